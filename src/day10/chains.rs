@@ -0,0 +1,253 @@
+// Day 10: Adapter Array - Chains
+// Works with the arrangements themselves, not just how many there are. For each adapter
+// we count the ways on from it to the device, the same look-behind as the 2nd star but
+// from the other end. Those counts steer everything here: enumeration never wanders down
+// a dead end, the k-th chain can be picked out directly, and picking k at random gives a
+// uniformly random chain.
+use rand::Rng;
+
+#[derive(Clone, Debug)]
+pub struct AdapterChains {
+    tolerance: i64,
+    // The outlet, every adapter in order, then the device.
+    joltages: Vec<i64>,
+    // ways[i]: chains from joltages[i] on to the device.
+    ways: Vec<u128>,
+}
+
+impl AdapterChains {
+    // Two adapters with the same rating can't both go in a chain, as each step has to go up.
+    // Rather than guess whether they're meant as one adapter or two, they're refused.
+    pub fn new(adapters: &[i32], tolerance: i32) -> Result<AdapterChains, String> {
+        if tolerance < 1 {
+            return Err(format!("A tolerance of {} doesn't let anything connect", tolerance));
+        }
+        if let Some(adapter) = adapters.iter().find(|adapter| **adapter <= 0) {
+            return Err(format!("Adapters are rated above the outlet's 0 jolts, not {}", adapter));
+        }
+
+        let tolerance = tolerance as i64;
+        let mut joltages: Vec<i64> = adapters.iter().map(|adapter| *adapter as i64).collect();
+        joltages.sort_unstable();
+        if let Some(pair) = joltages.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("Two adapters are rated {} jolts", pair[0]));
+        }
+        let device = joltages.last().copied().unwrap_or(0) + tolerance;
+        joltages.insert(0, 0);
+        joltages.push(device);
+
+        // Ratings strictly go up, so anything reachable from an adapter is a run straight after
+        // it, and a running total from the device backwards gives each count in constant time.
+        let len = joltages.len();
+        let mut ways = vec![0u128; len];
+        let mut ways_from = vec![0u128; len + 1];
+        ways[len - 1] = 1;
+        ways_from[len - 1] = 1;
+        let mut past_last = len;
+        for idx in (0..len - 1).rev() {
+            while past_last > idx + 1 && joltages[past_last - 1] - joltages[idx] > tolerance {
+                past_last -= 1;
+            }
+
+            ways[idx] = ways_from[idx + 1] - ways_from[past_last];
+            ways_from[idx] = ways_from[idx + 1].checked_add(ways[idx]).ok_or_else(|| "Too many chains to count".to_string())?;
+        }
+
+        Ok(AdapterChains { tolerance, joltages, ways })
+    }
+
+    pub fn tolerance(&self) -> i64 {
+        self.tolerance
+    }
+
+    pub fn device_joltage(&self) -> i64 {
+        *self.joltages.last().unwrap()
+    }
+
+    pub fn count(&self) -> u128 {
+        self.ways[0]
+    }
+
+    // Where the chain can go next from joltages[idx], lowest first, skipping dead ends.
+    fn next_steps(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let from = self.joltages[idx];
+        (idx + 1..self.joltages.len())
+            .take_while(move |next| self.joltages[*next] - from <= self.tolerance)
+            .filter(move |next| self.ways[*next] > 0)
+    }
+
+    // Every chain, from the outlet to the device, in lexicographic order.
+    pub fn iter(&self) -> ChainIter<'_> {
+        let stack = if self.count() > 0 { vec![ChainIter::stack_from(self, 0)] } else { Vec::new() };
+        ChainIter { chains: self, stack }
+    }
+
+    // The chain iter() would give at position rank.
+    pub fn chain_at(&self, rank: u128) -> Option<Vec<i64>> {
+        if rank >= self.count() {
+            return None;
+        }
+
+        let mut rank = rank;
+        let mut idx = 0;
+        let mut chain = vec![self.joltages[0]];
+        while idx < self.joltages.len() - 1 {
+            for next in self.next_steps(idx) {
+                if rank < self.ways[next] {
+                    idx = next;
+                    break;
+                }
+                rank -= self.ways[next];
+            }
+            chain.push(self.joltages[idx]);
+        }
+
+        Some(chain)
+    }
+
+    // Every chain is equally likely.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<Vec<i64>> {
+        if self.count() == 0 {
+            return None;
+        }
+        self.chain_at(rng.gen_range(0..self.count()))
+    }
+
+    pub fn is_chain(&self, chain: &[i64]) -> bool {
+        chain.first() == Some(&0) && chain.last() == Some(&self.device_joltage()) && chain.windows(2).all(|pair| pair[1] > pair[0] && pair[1] - pair[0] <= self.tolerance)
+    }
+}
+
+pub struct ChainIter<'a> {
+    chains: &'a AdapterChains,
+    // The chain so far, each step with the options still to try after it, reversed so the
+    // lowest is at the end.
+    stack: Vec<(usize, Vec<usize>)>,
+}
+
+impl<'a> ChainIter<'a> {
+    fn stack_from(chains: &AdapterChains, idx: usize) -> (usize, Vec<usize>) {
+        let mut options: Vec<usize> = chains.next_steps(idx).collect();
+        options.reverse();
+        (idx, options)
+    }
+}
+
+impl<'a> Iterator for ChainIter<'a> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        let device = self.chains.joltages.len() - 1;
+
+        loop {
+            let (idx, options) = self.stack.last_mut()?;
+            if *idx == device {
+                let chain = self.stack.iter().map(|(step, _)| self.chains.joltages[*step]).collect();
+                self.stack.pop();
+                return Some(chain);
+            }
+
+            match options.pop() {
+                Some(next) => {
+                    let step = ChainIter::stack_from(self.chains, next);
+                    self.stack.push(step);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+pub fn describe_chain(chain: &[i64]) -> String {
+    chain.iter().map(|joltage| joltage.to_string()).collect::<Vec<String>>().join(" → ")
+}
+
+#[cfg(test)]
+mod chainstests {
+    use super::*;
+    use crate::day10::{get_total_number_of_chains, get_total_number_of_chains_with_tolerance};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn get_test_data_small() -> Vec<i32> {
+        vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4]
+    }
+
+    #[test]
+    fn counts_match_the_second_star() {
+        let small = get_test_data_small();
+        let large = vec![28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35, 8, 17, 7, 9, 4, 2, 34, 10, 3];
+
+        assert_eq!(AdapterChains::new(&small, 3).unwrap().count(), 8);
+        assert_eq!(Ok(AdapterChains::new(&large, 3).unwrap().count() as i64), get_total_number_of_chains(&large));
+        for tolerance in 1..=5 {
+            assert_eq!(Ok(AdapterChains::new(&large, tolerance).unwrap().count() as i64), get_total_number_of_chains_with_tolerance(&large, tolerance));
+        }
+    }
+
+    #[test]
+    fn chains_come_out_in_order() {
+        let chains = AdapterChains::new(&get_test_data_small(), 3).unwrap();
+        let all: Vec<Vec<i64>> = chains.iter().collect();
+
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+        assert_eq!(all[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(all.iter().all(|chain| chains.is_chain(chain)));
+        for (rank, chain) in all.iter().enumerate() {
+            assert_eq!(chains.chain_at(rank as u128).as_ref(), Some(chain));
+        }
+        assert_eq!(chains.chain_at(8), None);
+    }
+
+    #[test]
+    fn enumeration_is_lazy() {
+        // About 10^20 chains; taking a few mustn't try to list them all.
+        let adapters: Vec<i32> = (1..=100).collect();
+        let chains = AdapterChains::new(&adapters, 3).unwrap();
+
+        assert!(chains.count() > 100_000_000_000_000_000_000);
+        let first: Vec<Vec<i64>> = chains.iter().take(3).collect();
+        assert_eq!(first[0], (0..=100).chain([103]).collect::<Vec<i64>>());
+        assert!(first[1] > first[0] && first[2] > first[1]);
+        assert_eq!(chains.chain_at(chains.count() - 1).unwrap().len(), 36);
+    }
+
+    #[test]
+    fn dead_ends_and_tolerance() {
+        // 1 → 5 is too far with the usual tolerance, so there's no chain at all.
+        assert_eq!(AdapterChains::new(&[1, 5], 3).unwrap().count(), 0);
+        assert_eq!(AdapterChains::new(&[1, 5], 3).unwrap().iter().next(), None);
+        assert_eq!(AdapterChains::new(&[1, 5], 4).unwrap().iter().collect::<Vec<Vec<i64>>>(), vec![vec![0, 1, 5, 9]]);
+        assert_eq!(AdapterChains::new(&[1, 5], 5).unwrap().iter().collect::<Vec<Vec<i64>>>(), vec![vec![0, 1, 5, 10], vec![0, 5, 10]]);
+
+        // 2 is the only way over the gap between 1 and 4.
+        let chains = AdapterChains::new(&[1, 2, 4], 2).unwrap();
+        assert_eq!(chains.iter().collect::<Vec<Vec<i64>>>(), vec![vec![0, 1, 2, 4, 6], vec![0, 2, 4, 6]]);
+
+        assert!(AdapterChains::new(&[1, 2], 0).is_err());
+        assert!(AdapterChains::new(&[0, 2], 3).is_err());
+        assert_eq!(AdapterChains::new(&[1, 4, 2, 4], 3).unwrap_err(), "Two adapters are rated 4 jolts");
+        assert_eq!(AdapterChains::new(&[], 3).unwrap().iter().collect::<Vec<Vec<i64>>>(), vec![vec![0, 3]]);
+    }
+
+    #[test]
+    fn sampling_is_uniform() {
+        let chains = AdapterChains::new(&get_test_data_small(), 3).unwrap();
+        let all: Vec<Vec<i64>> = chains.iter().collect();
+        let mut rng = StdRng::seed_from_u64(10);
+
+        let mut tally = vec![0; all.len()];
+        for _ in 0..8000 {
+            let chain = chains.sample(&mut rng).unwrap();
+            tally[all.iter().position(|other| *other == chain).unwrap()] += 1;
+        }
+
+        // 1000 each expected; a fair die lands well inside this.
+        assert!(tally.iter().all(|count| (850..1150).contains(count)), "{:?}", tally);
+        assert_eq!(AdapterChains::new(&[1, 5], 3).unwrap().sample(&mut rng), None);
+    }
+}
@@ -5,6 +5,12 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 use itertools::Itertools;
+use cursive::views::{EditView, LinearLayout};
+
+pub mod chains;
+
+// The most an adapter's rating can go up by from the one before, unless told otherwise.
+pub const DEFAULT_TOLERANCE: i32 = 3;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -20,6 +26,7 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Show Arrangements 🔗", 2);
         view.add_item("Back", 999);
     });
 }
@@ -32,6 +39,9 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            arrangements_prompt(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -79,7 +89,10 @@ pub fn second_star(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day10.txt").unwrap());
             let inputs: Vec<i32> = bufreader.lines().map(|line| line.unwrap().parse().unwrap()).collect();
 
-            Ok(format!("The total number of configurations is: {}", get_total_number_of_chains(&inputs)))
+            match get_total_number_of_chains(&inputs) {
+                Ok(total) => Ok(format!("The total number of configurations is: {}", total)),
+                Err(message) => Ok(message),
+            }
         },
         TextView::new,
     )
@@ -95,13 +108,76 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
+pub fn arrangements_prompt(s: &mut Cursive) {
+    let prompt = LinearLayout::vertical()
+        .child(TextView::new("Largest jump between adapters, in jolts:"))
+        .child(EditView::new().content(DEFAULT_TOLERANCE.to_string()).with_name("adapter_tolerance").fixed_width(10));
+
+    s.add_layer(
+        Dialog::around(prompt)
+            .title("Show Arrangements 🔗")
+            .button("Show", |s| {
+                let tolerance = s.call_on_name("adapter_tolerance", |view: &mut EditView| view.get_content()).unwrap();
+                match tolerance.trim().parse::<i32>() {
+                    Ok(tolerance) => {
+                        s.pop_layer();
+                        arrangements(s, tolerance);
+                    }
+                    Err(_) => s.add_layer(Dialog::info(format!("{} isn't a number of jolts", tolerance))),
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn arrangements(s: &mut Cursive, tolerance: i32) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let bufreader = BufReader::new(File::open("inputs/day10.txt").unwrap());
+            let inputs: Vec<i32> = bufreader.lines().map(|line| line.unwrap().parse().unwrap()).collect();
+
+            let adapter_chains = match chains::AdapterChains::new(&inputs, tolerance) {
+                Ok(adapter_chains) => adapter_chains,
+                Err(message) => return Ok(message),
+            };
+
+            let mut lines = vec![format!("{} arrangements with jumps of up to {} jolts.", adapter_chains.count(), tolerance), String::new(), "The first few:".to_string()];
+            lines.extend(adapter_chains.iter().take(3).map(|chain| chains::describe_chain(&chain)));
+            lines.push(String::new());
+            lines.push("A few at random:".to_string());
+            let mut rng = rand::thread_rng();
+            lines.extend((0..3).filter_map(|_| adapter_chains.sample(&mut rng)).map(|chain| chains::describe_chain(&chain)));
+
+            Ok(lines.join("\n"))
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(70);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Show Arrangements 🔗")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
 pub fn get_joltage_adapter_delta(adapter_a: i32, adapter_b: i32) -> i32 {
     adapter_b - adapter_a
 }
 
 pub fn can_joltage_adapters_connect(lower: i32, higher: i32) -> bool {
+    can_joltage_adapters_connect_within(lower, higher, DEFAULT_TOLERANCE)
+}
+
+pub fn can_joltage_adapters_connect_within(lower: i32, higher: i32, tolerance: i32) -> bool {
     let delta = get_joltage_adapter_delta(lower, higher);
-    delta <= 3 && delta > 0
+    delta <= tolerance && delta > 0
 }
 
 pub fn get_device_port_rating(adapters: &Vec<i32>) -> i32 {
@@ -152,34 +228,16 @@ pub fn is_valid_chain(start_joltage:i32, target_joltage: i32, adapter_chain: &Ve
     adapter_chain.iter().skip(1).filter(|adapter| { let fits = can_joltage_adapters_connect(last_adapter, **adapter); last_adapter = **adapter; fits}).count() == adapter_chain.len() - 1
 }
 
-pub fn get_total_number_of_chains(adapters: &Vec<i32>) -> i64 {
-    // first, sort adapter numbers descending
-    let mut sorted_adapters = adapters.into_iter().sorted().map(|a| *a).collect::<Vec<i32>>();
-    sorted_adapters.reverse();
-    sorted_adapters.push(0);
-
-    let mut known_chains = vec![1 as i64];
-
-    sorted_adapters.iter().enumerate().skip(1).for_each(|(idx, &_adapter)| {
-        let mut jumpback_matches: i64 = 0;
-
-        let look_behind_index = idx as i32 - 1;
-        if look_behind_index >= 0 {
-            jumpback_matches += if can_joltage_adapters_connect(sorted_adapters[idx], sorted_adapters[look_behind_index as usize]) { known_chains[look_behind_index as usize] } else { 0 };
-        }
-        let look_behind_index = idx as i32 - 2;
-        if look_behind_index >= 0 {
-            jumpback_matches += if can_joltage_adapters_connect(sorted_adapters[idx], sorted_adapters[look_behind_index as usize]) { known_chains[look_behind_index as usize] } else { 0 };
-        }
-        let look_behind_index = idx as i32 - 3;
-        if look_behind_index >= 0 {
-            jumpback_matches += if can_joltage_adapters_connect(sorted_adapters[idx], sorted_adapters[look_behind_index as usize]) { known_chains[look_behind_index as usize] } else { 0 };
-        }        
-        known_chains.push(jumpback_matches);
-        
-    });
+pub fn get_total_number_of_chains(adapters: &Vec<i32>) -> Result<i64, String> {
+    get_total_number_of_chains_with_tolerance(adapters, DEFAULT_TOLERANCE)
+}
 
-    *known_chains.last().unwrap() as i64
+pub fn get_total_number_of_chains_with_tolerance(adapters: &[i32], tolerance: i32) -> Result<i64, String> {
+    let total = chains::AdapterChains::new(adapters, tolerance)?.count();
+    if total > i64::MAX as u128 {
+        return Err(format!("{} chains won't fit an i64", total));
+    }
+    Ok(total as i64)
 }
 
 #[cfg(test)]
@@ -313,7 +371,11 @@ mod day10tests {
         let test_data = get_test_data_small();
         let test_data_large = get_test_data_large();
 
-        assert_eq!(get_total_number_of_chains(&test_data), 8);
-        assert_eq!(get_total_number_of_chains(&test_data_large), 19208);
+        assert_eq!(get_total_number_of_chains(&test_data), Ok(8));
+        assert_eq!(get_total_number_of_chains(&test_data_large), Ok(19208));
+
+        // 1 to 100 in steps of one has more chains than an i64 holds.
+        assert!(get_total_number_of_chains(&(1..=100).collect()).is_err());
+        assert!(get_total_number_of_chains(&vec![1, 2, 2]).is_err());
     }
 }
\ No newline at end of file
@@ -3,6 +3,10 @@
 use super::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use cursive::views::{EditView, ListView};
+
+pub mod slope_search;
+pub mod terrain;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -18,6 +22,8 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Slope Search 🔍", 2);
+        view.add_item("Terrain Costs 🪨", 3);
         view.add_item("Back", 999);
     });
 }
@@ -30,6 +36,12 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            slope_search_prompt(s);
+        }
+        3 => {
+            terrain_costs(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -107,27 +119,139 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
-pub fn count_trees_in_map(map_ylines: &Vec<String>, deltax: i32, deltay: i32, tree: char) -> i32 {
-    let mut x = 0;
-    let mut y = 0;
+pub fn slope_search_prompt(s: &mut Cursive) {
+    let bounds = ListView::new()
+        .child("Min right", EditView::new().content("0").with_name("min_dx").fixed_width(6))
+        .child("Max right", EditView::new().content("7").with_name("max_dx").fixed_width(6))
+        .child("Min down", EditView::new().content("1").with_name("min_dy").fixed_width(6))
+        .child("Max down", EditView::new().content("2").with_name("max_dy").fixed_width(6));
+
+    s.add_layer(
+        Dialog::around(bounds)
+            .title("Slope Search 🔍")
+            .button("Search", |s| {
+                let mut bounds = Vec::new();
+                for name in &["min_dx", "max_dx", "min_dy", "max_dy"] {
+                    let content = s.call_on_name(name, |view: &mut EditView| view.get_content()).unwrap();
+                    match content.trim().parse::<i32>() {
+                        Ok(bound) => bounds.push(bound),
+                        Err(_) => {
+                            s.add_layer(Dialog::info(format!("{} isn't a step size", content)));
+                            return;
+                        }
+                    }
+                }
+                s.pop_layer();
+                slope_search(s, bounds[0] ..= bounds[1], bounds[2] ..= bounds[3]);
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn slope_search(s: &mut Cursive, deltax_bounds: std::ops::RangeInclusive<i32>, deltay_bounds: std::ops::RangeInclusive<i32>) {
+    // Create async dialog for this.
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let bufreader = BufReader::new(File::open("inputs/day3_1.txt").unwrap());
+            let map_ylines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+
+            match slope_search::search_slopes(&map_ylines, deltax_bounds.clone(), deltay_bounds.clone(), '#') {
+                Ok(ranking) => Ok(format!("{}\n\n{}", ranking.summary(), ranking.table())),
+                Err(message) => Ok(format!("Can't search those slopes: {}", message)),
+            }
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(40);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Slope Search 🔍")
+            .button("Wheee!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn terrain_costs(s: &mut Cursive) {
+    // Create async dialog for this.
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let legend = match terrain::TerrainLegend::load("config/day3_terrain.toml") {
+                Ok(legend) => legend,
+                Err(message) => return Ok(message),
+            };
+
+            let bufreader = BufReader::new(File::open("inputs/day3_1.txt").unwrap());
+            let map_ylines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+
+            let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+
+            let costs: Vec<String> = slopes
+                .iter()
+                .map(|slope| match terrain::cost_along_slope(&map_ylines, slope.0, slope.1, &legend) {
+                    Ok(path_cost) => path_cost.describe(),
+                    Err(message) => message,
+                })
+                .collect();
+
+            Ok(costs.join("\n\n"))
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(40);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Terrain Costs 🪨")
+            .button("Ouch!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn count_trees_in_map(map_ylines: &[String], deltax: i32, deltay: i32, tree: char) -> i32 {
+    // Walked in i64 so big steps can't overflow, and wrapped onto each line as it's reached.
+    let mut x: i64 = 0;
+    let mut y: i64 = 0;
     let mut tree_count: i32 = 0;
     
-    while y + deltay < map_ylines.len() as i32 {
-        y += deltay;
-        x += deltax;
+    while y + (deltay as i64) < map_ylines.len() as i64 {
+        y += deltay as i64;
+        x += deltax as i64;
         
-        if is_tree_at_location(&map_ylines[y as usize], x, tree) {
+        let yline = &map_ylines[y as usize];
+        if is_tree_at_location(yline, wrap_x(yline, x), tree) {
             tree_count += 1;
         }
     }
     tree_count
 }
 
+// Where x lands on this line once it's wrapped round, as terrain_at_location would.
+pub fn wrap_x(yline: &str, x: i64) -> i32 {
+    x.rem_euclid(yline.chars().count().max(1) as i64) as i32
+}
+
 pub fn is_tree_at_location(yline: &String, x: i32, tree: char) -> bool {
-    // Wrap X.
-    let wrapped_x = x % yline.len() as i32;
+    terrain_at_location(yline, x) == Some(tree)
+}
+
+pub fn terrain_at_location(yline: &str, x: i32) -> Option<char> {
+    if yline.is_empty() {
+        return None;
+    }
+
+    // Wrap X. Euclidean remainder so slopes heading left wrap too.
+    let wrapped_x = x.rem_euclid(yline.chars().count() as i32);
 
-    yline.chars().nth(wrapped_x as usize) == Some(tree)
+    yline.chars().nth(wrapped_x as usize)
 }
 
 pub fn map_all_slopes_multiplied_together(map_ylines: &Vec<String>, slopes: &Vec<(i32, i32)>, tree: char) -> i64 {
@@ -175,6 +299,8 @@ mod day3tests {
             ".#..#...#.#".to_string()];
         
         assert_eq!(day3::count_trees_in_map(&test_entries, 3, 1, '#'), 7);
+        assert_eq!(day3::count_trees_in_map(&test_entries, i32::MIN, 1, '#'), day3::count_trees_in_map(&test_entries, i32::MIN.rem_euclid(11), 1, '#'));
+        assert_eq!(day3::count_trees_in_map(&test_entries, 3, i32::MAX, '#'), 0);
     }
 
     #[test]
@@ -0,0 +1,186 @@
+// Day 3: Toboggan Trajectory - Slope Search
+// Tries every slope inside a pair of bounds and ranks them by trees thumped.
+use super::count_trees_in_map;
+use std::ops::RangeInclusive;
+use std::thread;
+
+// Every slope is scored and kept, so the bounds can't cover more than this many.
+pub const MAX_SLOPES: i64 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlopeScore {
+    pub deltax: i32,
+    pub deltay: i32,
+    pub trees: i32,
+}
+
+pub struct SlopeRanking {
+    // Sorted from fewest trees to most; ties are ordered by (deltay, deltax).
+    pub scores: Vec<SlopeScore>,
+}
+
+impl SlopeRanking {
+    pub fn best(&self) -> Vec<SlopeScore> {
+        match self.scores.first() {
+            Some(first) => self.scores.iter().filter(|score| score.trees == first.trees).copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn worst(&self) -> Vec<SlopeScore> {
+        match self.scores.last() {
+            Some(last) => self.scores.iter().filter(|score| score.trees == last.trees).copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn table(&self) -> String {
+        let mut table = String::from("Rank  Right  Down  Trees\n");
+        let mut rank = 0;
+        let mut last_trees = None;
+
+        for (idx, score) in self.scores.iter().enumerate() {
+            // Tied slopes share a rank.
+            if last_trees != Some(score.trees) {
+                rank = idx + 1;
+                last_trees = Some(score.trees);
+            }
+            table.push_str(&format!("{:>4}  {:>5}  {:>4}  {:>5}\n", rank, score.deltax, score.deltay, score.trees));
+        }
+
+        table
+    }
+
+    pub fn summary(&self) -> String {
+        let describe = |scores: Vec<SlopeScore>| scores.iter().map(|score| format!("({}, {})", score.deltax, score.deltay)).collect::<Vec<String>>().join(", ");
+
+        let best = self.best();
+        let worst = self.worst();
+
+        format!(
+            "Slopes searched: {}\nBest ({} 🎄): {}\nWorst ({} 🎄): {}",
+            self.scores.len(),
+            best.first().map_or(0, |score| score.trees),
+            describe(best),
+            worst.first().map_or(0, |score| score.trees),
+            describe(worst)
+        )
+    }
+}
+
+pub fn search_slopes(map_ylines: &[String], deltax_bounds: RangeInclusive<i32>, deltay_bounds: RangeInclusive<i32>, tree: char) -> Result<SlopeRanking, String> {
+    if *deltay_bounds.start() < 1 {
+        return Err(format!("Down must be at least 1, got {}", deltay_bounds.start()));
+    }
+
+    let span = |bounds: &RangeInclusive<i32>| (*bounds.end() as i64 - *bounds.start() as i64 + 1).max(0);
+    let slope_count = span(&deltax_bounds) * span(&deltay_bounds);
+    if slope_count > MAX_SLOPES {
+        return Err(format!("Those bounds cover {} slopes; keep it to {} or fewer", slope_count, MAX_SLOPES));
+    }
+
+    let slopes: Vec<(i32, i32)> = deltay_bounds
+        .flat_map(|deltay| deltax_bounds.clone().map(move |deltax| (deltax, deltay)))
+        .collect();
+
+    if slopes.is_empty() {
+        return Err("No slopes inside those bounds".to_string());
+    }
+
+    let thread_count = thread::available_parallelism().map_or(1, |count| count.get()).min(slopes.len());
+    let chunk_size = slopes.len().div_ceil(thread_count);
+
+    // Each thread takes a chunk of slopes; the map itself is shared read-only.
+    let mut scores: Vec<SlopeScore> = thread::scope(|scope| {
+        let workers: Vec<_> = slopes
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&(deltax, deltay)| SlopeScore { deltax, deltay, trees: count_trees_in_map(map_ylines, deltax, deltay, tree) })
+                        .collect::<Vec<SlopeScore>>()
+                })
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    scores.sort_by_key(|score| (score.trees, score.deltay, score.deltax));
+
+    Ok(SlopeRanking { scores })
+}
+
+#[cfg(test)]
+mod slopesearchtests {
+    use super::*;
+
+    fn get_test_map() -> Vec<String> {
+        vec![
+            "..##.......".to_string(),
+            "#...#...#..".to_string(),
+            ".#....#..#.".to_string(),
+            "..#.#...#.#".to_string(),
+            ".#...##..#.".to_string(),
+            "..#.##.....".to_string(),
+            ".#.#.#....#".to_string(),
+            ".#........#".to_string(),
+            "#.##...#...".to_string(),
+            "#...##....#".to_string(),
+            ".#..#...#.#".to_string(),
+        ]
+    }
+
+    #[test]
+    fn search_matches_puzzle_slopes() {
+        let ranking = search_slopes(&get_test_map(), 1..=7, 1..=2, '#').unwrap();
+        let trees_for = |deltax, deltay| ranking.scores.iter().find(|score| score.deltax == deltax && score.deltay == deltay).unwrap().trees;
+
+        assert_eq!(ranking.scores.len(), 14);
+        assert_eq!(trees_for(1, 1), 2);
+        assert_eq!(trees_for(3, 1), 7);
+        assert_eq!(trees_for(5, 1), 3);
+        assert_eq!(trees_for(7, 1), 4);
+        assert_eq!(trees_for(1, 2), 2);
+    }
+
+    #[test]
+    fn ranking_reports_ties() {
+        let ranking = search_slopes(&get_test_map(), 1..=7, 1..=2, '#').unwrap();
+
+        assert_eq!(ranking.best(), vec![SlopeScore { deltax: 5, deltay: 2, trees: 0 }]);
+        assert_eq!(ranking.worst(), vec![SlopeScore { deltax: 3, deltay: 1, trees: 7 }]);
+
+        let ranking = search_slopes(&get_test_map(), 2..=2, 1..=2, '#').unwrap();
+
+        assert_eq!(ranking.best().len(), 2);
+        assert_eq!(ranking.best(), ranking.worst());
+        assert_eq!(ranking.table().lines().count(), 3);
+        assert!(ranking.table().lines().skip(1).all(|line| line.trim_start().starts_with('1')));
+    }
+
+    #[test]
+    fn invalid_bounds_are_rejected() {
+        assert!(search_slopes(&get_test_map(), 1..=3, 0..=2, '#').is_err());
+
+        // Bounds typed the wrong way round leave nothing to search.
+        let (min_dx, max_dx) = (3, 1);
+        assert!(search_slopes(&get_test_map(), min_dx..=max_dx, 1..=2, '#').is_err());
+
+        assert_eq!(
+            search_slopes(&get_test_map(), i32::MIN..=i32::MAX, 1..=2, '#').err(),
+            Some(format!("Those bounds cover {} slopes; keep it to {} or fewer", 1u64 << 33, MAX_SLOPES))
+        );
+    }
+
+    #[test]
+    fn huge_steps_wrap_instead_of_overflowing() {
+        let ranking = search_slopes(&get_test_map(), i32::MAX - 1..=i32::MAX, i32::MAX - 1..=i32::MAX, '#').unwrap();
+        assert_eq!(ranking.scores.len(), 4);
+        assert!(ranking.scores.iter().all(|score| score.trees == 0));
+
+        let ranking = search_slopes(&get_test_map(), i32::MAX..=i32::MAX, 1..=1, '#').unwrap();
+        assert_eq!(ranking.scores[0].trees, count_trees_in_map(&get_test_map(), i32::MAX % 11, 1, '#'));
+    }
+}
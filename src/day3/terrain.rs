@@ -0,0 +1,224 @@
+// Day 3: Toboggan Trajectory - Weighted Terrain
+// Generalises "count the trees" into a cost per terrain type, loaded from a legend file.
+use super::{terrain_at_location, wrap_x};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Deserialize)]
+struct TerrainConfig {
+    terrain: BTreeMap<String, TerrainEntry>,
+}
+
+#[derive(Deserialize)]
+struct TerrainEntry {
+    symbol: char,
+    cost: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerrainKind {
+    pub name: String,
+    pub symbol: char,
+    pub cost: i64,
+}
+
+pub struct TerrainLegend {
+    kinds: Vec<TerrainKind>,
+}
+
+impl TerrainLegend {
+    pub fn load(path: &str) -> Result<TerrainLegend, String> {
+        let contents = fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+        TerrainLegend::from_toml_str(&contents)
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<TerrainLegend, String> {
+        let config: TerrainConfig = toml::from_str(contents).map_err(|err| format!("Bad terrain legend: {}", err))?;
+
+        let mut kinds: Vec<TerrainKind> = Vec::new();
+        for (name, entry) in config.terrain {
+            if let Some(existing) = kinds.iter().find(|kind| kind.symbol == entry.symbol) {
+                return Err(format!("'{}' is used by both {} and {}", entry.symbol, existing.name, name));
+            }
+            kinds.push(TerrainKind { name, symbol: entry.symbol, cost: entry.cost });
+        }
+
+        Ok(TerrainLegend { kinds })
+    }
+
+    // The legend the original puzzle uses: one kind of obstacle, costing one per hit.
+    pub fn trees_only(tree: char) -> TerrainLegend {
+        TerrainLegend { kinds: vec![TerrainKind { name: "tree".to_string(), symbol: tree, cost: 1 }] }
+    }
+
+    pub fn kinds(&self) -> &[TerrainKind] {
+        &self.kinds
+    }
+
+    pub fn lookup(&self, symbol: char) -> Option<&TerrainKind> {
+        self.kinds.iter().find(|kind| kind.symbol == symbol)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerrainTally {
+    pub name: String,
+    pub symbol: char,
+    pub hits: i32,
+    pub cost: i64,
+}
+
+pub struct PathCost {
+    pub deltax: i32,
+    pub deltay: i32,
+    pub total: i64,
+    // One entry per kind in the legend, sorted by name, including kinds that were never hit.
+    pub breakdown: Vec<TerrainTally>,
+}
+
+impl PathCost {
+    pub fn describe(&self) -> String {
+        let mut description = format!("({}, {}) costs {}", self.deltax, self.deltay, self.total);
+        for tally in &self.breakdown {
+            description.push_str(&format!("\n  {} '{}': {} hit(s) = {}", tally.name, tally.symbol, tally.hits, tally.cost));
+        }
+        description
+    }
+}
+
+pub fn cost_along_slope(map_ylines: &[String], deltax: i32, deltay: i32, legend: &TerrainLegend) -> Result<PathCost, String> {
+    let mut breakdown: Vec<TerrainTally> = legend
+        .kinds()
+        .iter()
+        .map(|kind| TerrainTally { name: kind.name.to_string(), symbol: kind.symbol, hits: 0, cost: 0 })
+        .collect();
+
+    let mut x: i64 = 0;
+    let mut y: i64 = 0;
+
+    // Same walk as count_trees_in_map: the starting square is never counted.
+    while y + (deltay as i64) < map_ylines.len() as i64 {
+        y += deltay as i64;
+        x += deltax as i64;
+
+        let yline = &map_ylines[y as usize];
+        if let Some(terrain) = terrain_at_location(yline, wrap_x(yline, x)) {
+            if let Some(tally) = breakdown.iter_mut().find(|tally| tally.symbol == terrain) {
+                tally.hits += 1;
+                tally.cost = tally
+                    .cost
+                    .checked_add(legend.lookup(terrain).unwrap().cost)
+                    .ok_or_else(|| format!("The {} cost along ({}, {}) is too big to add up", tally.name, deltax, deltay))?;
+            }
+        }
+    }
+
+    let total = breakdown
+        .iter()
+        .try_fold(0i64, |total, tally| total.checked_add(tally.cost))
+        .ok_or_else(|| format!("The total cost along ({}, {}) is too big to add up", deltax, deltay))?;
+
+    Ok(PathCost { deltax, deltay, total, breakdown })
+}
+
+#[cfg(test)]
+mod terraintests {
+    use super::*;
+
+    fn get_test_legend() -> TerrainLegend {
+        TerrainLegend::from_toml_str(
+            r##"
+            [terrain.tree]
+            symbol = "#"
+            cost = 1
+
+            [terrain.rock]
+            symbol = "^"
+            cost = 5
+
+            [terrain.ice]
+            symbol = "~"
+            cost = 0
+            "##,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn legend_parsing_works() {
+        let legend = get_test_legend();
+
+        assert_eq!(legend.kinds().len(), 3);
+        assert_eq!(legend.lookup('^').unwrap().cost, 5);
+        assert_eq!(legend.lookup('~').unwrap().name, "ice");
+        assert_eq!(legend.lookup('.'), None);
+
+        let clashing = "[terrain.tree]\nsymbol = \"#\"\ncost = 1\n[terrain.wall]\nsymbol = \"#\"\ncost = 9\n";
+        assert!(TerrainLegend::from_toml_str(clashing).is_err());
+        assert!(TerrainLegend::from_toml_str("[terrain.tree]\nsymbol = \"##\"\ncost = 1\n").is_err());
+    }
+
+    #[test]
+    fn bundled_legend_loads() {
+        let legend = TerrainLegend::load("config/day3_terrain.toml").unwrap();
+
+        assert_eq!(legend.lookup('#').unwrap().cost, 1);
+    }
+
+    #[test]
+    fn path_cost_works() {
+        let test_map = vec![
+            "..^~".to_string(),
+            ".#..".to_string(),
+            "..^.".to_string(),
+            "...~".to_string(),
+            "^...".to_string(),
+        ];
+
+        let path_cost = cost_along_slope(&test_map, 1, 1, &get_test_legend()).unwrap();
+
+        assert_eq!(path_cost.total, 11);
+        assert_eq!(path_cost.breakdown[0], TerrainTally { name: "ice".to_string(), symbol: '~', hits: 1, cost: 0 });
+        assert_eq!(path_cost.breakdown[1], TerrainTally { name: "rock".to_string(), symbol: '^', hits: 2, cost: 10 });
+        assert_eq!(path_cost.breakdown[2], TerrainTally { name: "tree".to_string(), symbol: '#', hits: 1, cost: 1 });
+    }
+
+    #[test]
+    fn trees_only_matches_tree_count() {
+        let test_map = vec![
+            "..##.......".to_string(),
+            "#...#...#..".to_string(),
+            ".#....#..#.".to_string(),
+            "..#.#...#.#".to_string(),
+            ".#...##..#.".to_string(),
+            "..#.##.....".to_string(),
+            ".#.#.#....#".to_string(),
+            ".#........#".to_string(),
+            "#.##...#...".to_string(),
+            "#...##....#".to_string(),
+            ".#..#...#.#".to_string(),
+        ];
+
+        let path_cost = cost_along_slope(&test_map, 3, 1, &TerrainLegend::trees_only('#')).unwrap();
+
+        assert_eq!(path_cost.total, 7);
+        assert_eq!(path_cost.breakdown[0].hits, 7);
+    }
+
+    #[test]
+    fn overflowing_costs_are_reported() {
+        let test_map = vec!["..".to_string(), "#^".to_string(), "#^".to_string()];
+        let legend = TerrainLegend::from_toml_str(
+            "[terrain.tree]\nsymbol = \"#\"\ncost = 9223372036854775807\n[terrain.rock]\nsymbol = \"^\"\ncost = 1\n",
+        )
+        .unwrap();
+
+        // One tree and one rock: each tally fits, the total doesn't.
+        assert!(cost_along_slope(&test_map, 1, 1, &legend).is_err());
+        // Two trees: the tree tally itself overflows.
+        assert!(cost_along_slope(&test_map, 0, 1, &legend).is_err());
+        // A big step wraps round rather than overflowing x.
+        assert_eq!(cost_along_slope(&test_map, i32::MAX, 1, &TerrainLegend::trees_only('#')).unwrap().total, 1);
+    }
+}
@@ -0,0 +1,233 @@
+// Day 4: Passport Processing - Typed Fields
+// Every field keeps the text it was read from, so a passport with bad values still
+// writes back out unchanged. The typed value is only there when the text parses.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field<T> {
+    raw: String,
+    value: Option<T>,
+}
+
+impl<T: FromStr> Field<T> {
+    // The only way to make a field, so the value is always what the text parses to.
+    pub fn parse(raw: &str) -> Field<T> {
+        Field { raw: raw.to_string(), value: raw.parse().ok() }
+    }
+}
+
+impl<T> Field<T> {
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+}
+
+// The number and unit a range rule checks, for the field types that carry one.
+pub trait Measured {
+    fn measure(&self) -> (i64, &str);
+}
+
+impl Measured for i32 {
+    fn measure(&self) -> (i64, &str) {
+        (*self as i64, "")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightUnit {
+    Centimetres,
+    Inches,
+}
+
+impl HeightUnit {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            HeightUnit::Centimetres => "cm",
+            HeightUnit::Inches => "in",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Height {
+    pub value: u32,
+    pub unit: HeightUnit,
+}
+
+impl FromStr for Height {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Height, String> {
+        let (number, unit) = if let Some(number) = text.strip_suffix("cm") {
+            (number, HeightUnit::Centimetres)
+        } else if let Some(number) = text.strip_suffix("in") {
+            (number, HeightUnit::Inches)
+        } else {
+            return Err(format!("{} has no cm/in unit", text));
+        };
+
+        if number.is_empty() || !number.chars().all(|chr| chr.is_ascii_digit()) {
+            return Err(format!("{} is not a number", number));
+        }
+
+        Ok(Height { value: number.parse().map_err(|_| format!("{} is too large", number))?, unit })
+    }
+}
+
+impl Measured for Height {
+    fn measure(&self) -> (i64, &str) {
+        (self.value as i64, self.unit.suffix())
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.suffix())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HairColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl FromStr for HairColor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<HairColor, String> {
+        let hex = text.strip_prefix('#').ok_or_else(|| format!("{} doesn't start with #", text))?;
+        if hex.len() != 6 || !hex.chars().all(|chr| chr.is_ascii_hexdigit()) {
+            return Err(format!("{} isn't six hex digits", hex));
+        }
+
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap();
+        Ok(HairColor { r: channel(0), g: channel(2), b: channel(4) })
+    }
+}
+
+impl fmt::Display for HairColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EyeColor {
+    Amber,
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Hazel,
+    Other,
+}
+
+impl FromStr for EyeColor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<EyeColor, String> {
+        match text {
+            "amb" => Ok(EyeColor::Amber),
+            "blu" => Ok(EyeColor::Blue),
+            "brn" => Ok(EyeColor::Brown),
+            "gry" => Ok(EyeColor::Gray),
+            "grn" => Ok(EyeColor::Green),
+            "hzl" => Ok(EyeColor::Hazel),
+            "oth" => Ok(EyeColor::Other),
+            _ => Err(format!("{} isn't an eye color", text)),
+        }
+    }
+}
+
+impl fmt::Display for EyeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = match self {
+            EyeColor::Amber => "amb",
+            EyeColor::Blue => "blu",
+            EyeColor::Brown => "brn",
+            EyeColor::Gray => "gry",
+            EyeColor::Green => "grn",
+            EyeColor::Hazel => "hzl",
+            EyeColor::Other => "oth",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+// Kept as text because leading zeros matter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassportId(String);
+
+impl PassportId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PassportId {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<PassportId, String> {
+        if text.is_empty() || !text.chars().all(|chr| chr.is_ascii_digit()) {
+            return Err(format!("{} isn't all digits", text));
+        }
+        Ok(PassportId(text.to_string()))
+    }
+}
+
+impl fmt::Display for PassportId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod fieldstests {
+    use super::*;
+
+    #[test]
+    fn height_parsing_works() {
+        assert_eq!("183cm".parse::<Height>(), Ok(Height { value: 183, unit: HeightUnit::Centimetres }));
+        assert_eq!("59in".parse::<Height>(), Ok(Height { value: 59, unit: HeightUnit::Inches }));
+        assert!("170".parse::<Height>().is_err());
+        assert!("cm".parse::<Height>().is_err());
+        assert!("-3in".parse::<Height>().is_err());
+        assert_eq!(Height { value: 76, unit: HeightUnit::Inches }.to_string(), "76in");
+    }
+
+    #[test]
+    fn hair_color_parsing_works() {
+        assert_eq!("#fffffd".parse::<HairColor>(), Ok(HairColor { r: 255, g: 255, b: 253 }));
+        assert!("fffffd".parse::<HairColor>().is_err());
+        assert!("#12 abc".parse::<HairColor>().is_err());
+        assert_eq!(HairColor { r: 0xcf, g: 0xa0, b: 0x7d }.to_string(), "#cfa07d");
+    }
+
+    #[test]
+    fn eye_color_and_id_parsing_works() {
+        assert_eq!("hzl".parse::<EyeColor>(), Ok(EyeColor::Hazel));
+        assert!("zzz".parse::<EyeColor>().is_err());
+        assert_eq!(EyeColor::Gray.to_string(), "gry");
+
+        assert_eq!("028048884".parse::<PassportId>().unwrap().as_str(), "028048884");
+        assert!("#12345".parse::<PassportId>().is_err());
+    }
+
+    #[test]
+    fn fields_keep_their_raw_text() {
+        let field: Field<i32> = Field::parse("0012");
+        assert_eq!(field.raw(), "0012");
+        assert_eq!(field.value(), Some(&12));
+
+        let field: Field<Height> = Field::parse("190");
+        assert_eq!(field.raw(), "190");
+        assert_eq!(field.value(), None);
+    }
+}
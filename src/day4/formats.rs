@@ -0,0 +1,291 @@
+// Day 4: Passport Processing - Import/Export
+// Reads and writes passport batches in the puzzle's own format, as JSON, and as CSV.
+use super::Passport;
+use serde_json::{Map, Value};
+
+pub const KNOWN_KEYS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+pub struct PassportBatch {
+    pub passports: Vec<Passport>,
+    pub trailing_newline: bool,
+}
+
+// A single record: key:value pairs separated by spaces or line breaks.
+// A token with no colon is kept as a key with an empty value.
+pub fn parse_record(record: &str) -> Passport {
+    let mut passport = Passport::new();
+
+    for line in record.lines() {
+        for token in line.split_whitespace() {
+            let (key, value) = token.split_once(':').unwrap_or((token, ""));
+            passport.set_field(key, value);
+        }
+        passport.mark_line_break();
+    }
+
+    // The record itself doesn't end in a line break; the batch separator handles that.
+    if let Some(last) = passport.layout.last_mut() {
+        last.1 = false;
+    }
+
+    passport
+}
+
+pub fn write_record(passport: &Passport) -> String {
+    let mut record = String::new();
+
+    for (key, value, line_break) in passport.entries() {
+        if !record.is_empty() && !record.ends_with('\n') {
+            record.push(' ');
+        }
+        record.push_str(key);
+        record.push(':');
+        record.push_str(value);
+        if line_break {
+            record.push('\n');
+        }
+    }
+
+    record.trim_end_matches('\n').to_string()
+}
+
+pub fn parse_batch(text: &str) -> PassportBatch {
+    let mut passports = Vec::new();
+    let mut current_record: Vec<&str> = Vec::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current_record.is_empty() {
+                passports.push(parse_record(&current_record.join("\n")));
+                current_record.clear();
+            }
+        } else {
+            current_record.push(line);
+        }
+    }
+
+    if !current_record.is_empty() {
+        passports.push(parse_record(&current_record.join("\n")));
+    }
+
+    PassportBatch { passports, trailing_newline: text.ends_with('\n') }
+}
+
+pub fn write_batch(batch: &PassportBatch) -> String {
+    let mut text = batch.passports.iter().map(write_record).collect::<Vec<String>>().join("\n\n");
+    if batch.trailing_newline {
+        text.push('\n');
+    }
+    text
+}
+
+// Keys come out in the order the record had them; serde_json is built with preserve_order.
+pub fn to_json(passports: &[Passport]) -> String {
+    let records: Vec<Value> = passports
+        .iter()
+        .map(|passport| {
+            let mut record = Map::new();
+            for (key, value, _) in passport.entries() {
+                record.insert(key.to_string(), Value::String(value.to_string()));
+            }
+            Value::Object(record)
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&Value::Array(records)).unwrap()
+}
+
+pub fn from_json(json: &str) -> Result<Vec<Passport>, String> {
+    let records: Vec<Map<String, Value>> = serde_json::from_str(json).map_err(|err| format!("Bad passport JSON: {}", err))?;
+
+    records
+        .iter()
+        .enumerate()
+        .map(|(idx, record)| {
+            let mut passport = Passport::new();
+            for (key, value) in record {
+                match value {
+                    Value::String(text) => passport.set_field(key, text),
+                    Value::Number(number) => passport.set_field(key, &number.to_string()),
+                    _ => return Err(format!("Passport {}: {} should be a string", idx + 1, key)),
+                }
+            }
+            Ok(passport)
+        })
+        .collect()
+}
+
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Known keys come first, then any extra keys in the order they first appear.
+// Missing fields are written as empty cells.
+pub fn to_csv(passports: &[Passport]) -> String {
+    let mut columns: Vec<String> = KNOWN_KEYS.iter().map(|key| key.to_string()).collect();
+    for passport in passports {
+        for (key, _) in &passport.extra {
+            if !columns.contains(key) {
+                columns.push(key.to_string());
+            }
+        }
+    }
+
+    let mut csv = columns.iter().map(|column| csv_cell(column)).collect::<Vec<String>>().join(",");
+    csv.push('\n');
+
+    for passport in passports {
+        let row: Vec<String> = columns.iter().map(|column| csv_cell(passport.get_field(column).unwrap_or(""))).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn parse_csv_rows(csv: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(chr) = chars.next() {
+        if in_quotes {
+            match chr {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    cell.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => cell.push(chr),
+            }
+        } else {
+            match chr {
+                '"' if cell.is_empty() => in_quotes = true,
+                ',' => row.push(std::mem::take(&mut cell)),
+                '\r' => {}
+                '\n' => {
+                    row.push(std::mem::take(&mut cell));
+                    rows.push(std::mem::take(&mut row));
+                }
+                _ => cell.push(chr),
+            }
+        }
+    }
+
+    if in_quotes {
+        return Err("Unterminated quote in passport CSV".to_string());
+    }
+
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+pub fn from_csv(csv: &str) -> Result<Vec<Passport>, String> {
+    let mut rows = parse_csv_rows(csv)?.into_iter();
+    let header = rows.next().ok_or_else(|| "Passport CSV has no header".to_string())?;
+
+    rows.enumerate()
+        .map(|(idx, row)| {
+            if row.len() != header.len() {
+                return Err(format!("Row {} has {} cells, expected {}", idx + 2, row.len(), header.len()));
+            }
+
+            let mut passport = Passport::new();
+            for (key, value) in header.iter().zip(row.iter()) {
+                if !value.is_empty() {
+                    passport.set_field(key, value);
+                }
+            }
+            Ok(passport)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod formatstests {
+    use super::*;
+    use crate::day4::fields::{EyeColor, HairColor, Height, HeightUnit};
+
+    fn get_test_batch() -> &'static str {
+        "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd\nbyr:1937 iyr:2017 cid:147 hgt:183cm\n\niyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884\nhcl:#cfa07d byr:1929\n\nhcl:#ae17e1 iyr:2013\neyr:2024\necl:brn pid:760753108 byr:1931\nhgt:179cm\n\nhcl:#cfa07d eyr:2025 pid:166559648\niyr:2011 ecl:brn hgt:59in\n"
+    }
+
+    #[test]
+    fn record_parses_into_typed_fields() {
+        let passport = parse_record("ecl:gry pid:860033327 eyr:2020 hcl:#fffffd\nbyr:1937 iyr:2017 cid:147 hgt:183cm zzz:1");
+
+        assert_eq!(passport.byr.as_ref().unwrap().value(), Some(&1937));
+        assert_eq!(passport.hgt.as_ref().unwrap().value(), Some(&Height { value: 183, unit: HeightUnit::Centimetres }));
+        assert_eq!(passport.hcl.as_ref().unwrap().value(), Some(&HairColor { r: 0xff, g: 0xff, b: 0xfd }));
+        assert_eq!(passport.ecl.as_ref().unwrap().value(), Some(&EyeColor::Gray));
+        assert_eq!(passport.pid.as_ref().unwrap().value().unwrap().as_str(), "860033327");
+        assert_eq!(passport.cid, Some("147".to_string()));
+        assert_eq!(passport.extra, vec![("zzz".to_string(), "1".to_string())]);
+    }
+
+    #[test]
+    fn batch_round_trips_byte_for_byte() {
+        let batch = parse_batch(get_test_batch());
+
+        assert_eq!(batch.passports.len(), 4);
+        assert_eq!(write_batch(&batch), get_test_batch());
+
+        let odd_batch = "hgt:170 byr:abc\nnokey\n\nhcl:dab227 ecl:zzz:1";
+        assert_eq!(write_batch(&parse_batch(odd_batch)), "hgt:170 byr:abc\nnokey:\n\nhcl:dab227 ecl:zzz:1");
+    }
+
+    #[test]
+    fn puzzle_input_round_trips() {
+        let text = std::fs::read_to_string("inputs/day4_1.txt").unwrap();
+
+        assert_eq!(write_batch(&parse_batch(&text)), text);
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let batch = parse_batch(get_test_batch());
+        let json = to_json(&batch.passports);
+        let imported = from_json(&json).unwrap();
+
+        assert_eq!(imported.len(), 4);
+        for (original, imported) in batch.passports.iter().zip(imported.iter()) {
+            for key in KNOWN_KEYS.iter() {
+                assert_eq!(original.get_field(key), imported.get_field(key));
+            }
+        }
+
+        let keys: Vec<&str> = imported[0].entries().iter().map(|(key, _, _)| *key).collect();
+        assert_eq!(keys, vec!["ecl", "pid", "eyr", "hcl", "byr", "iyr", "cid", "hgt"]);
+
+        assert!(from_json("[{\"byr\": [1, 2]}]").is_err());
+        assert_eq!(from_json("[{\"byr\": 1937}]").unwrap()[0].byr.as_ref().unwrap().value(), Some(&1937));
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        let mut passports = parse_batch(get_test_batch()).passports;
+        passports[0].set_field("note", "has, a \"comma\"");
+
+        let csv = to_csv(&passports);
+        assert_eq!(csv.lines().next().unwrap(), "byr,iyr,eyr,hgt,hcl,ecl,pid,cid,note");
+
+        let imported = from_csv(&csv).unwrap();
+        assert_eq!(imported.len(), 4);
+        assert_eq!(imported[0].get_field("note"), Some("has, a \"comma\""));
+        assert_eq!(imported[1].get_field("hgt"), None);
+        assert_eq!(imported[3].get_field("hgt"), Some("59in"));
+
+        assert!(from_csv("byr,iyr\n1937\n").is_err());
+        assert!(from_csv("byr\n\"1937\n").is_err());
+    }
+}
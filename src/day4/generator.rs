@@ -0,0 +1,353 @@
+// Day 4: Passport Processing - Synthetic Batches
+// Writes passport batches with a known mix of good and bad records, plus a manifest
+// saying what each record should validate as. Expectations come from how each record
+// was built, not from the validation code it's meant to test.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+const REQUIRED_KEYS: [&str; 7] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"];
+const EYE_COLORS: [&str; 7] = ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum RecordKind {
+    Valid,
+    MissingField,
+    OutOfRange,
+    MalformedUnit,
+    MalformedValue,
+}
+
+pub struct GeneratorConfig {
+    pub count: usize,
+    pub seed: u64,
+    // Relative weights for each kind of record.
+    pub valid_weight: u32,
+    pub missing_field_weight: u32,
+    pub out_of_range_weight: u32,
+    pub malformed_unit_weight: u32,
+    pub malformed_value_weight: u32,
+    // Chance of adding unknown keys to a record; they never change its validity.
+    pub extra_key_chance: f64,
+    // Chance of wrapping a record over several lines with stray whitespace.
+    pub odd_wrapping_chance: f64,
+}
+
+impl GeneratorConfig {
+    pub fn new(count: usize, seed: u64) -> GeneratorConfig {
+        GeneratorConfig {
+            count,
+            seed,
+            valid_weight: 4,
+            missing_field_weight: 2,
+            out_of_range_weight: 2,
+            malformed_unit_weight: 1,
+            malformed_value_weight: 1,
+            extra_key_chance: 0.2,
+            odd_wrapping_chance: 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExpectedRecord {
+    pub index: usize,
+    pub kind: RecordKind,
+    // The field that was broken, if any.
+    pub field: Option<String>,
+    pub valid_star1: bool,
+    pub valid_star2: bool,
+}
+
+pub struct GeneratedBatch {
+    pub text: String,
+    pub manifest: Vec<ExpectedRecord>,
+}
+
+impl GeneratedBatch {
+    pub fn manifest_json(&self) -> String {
+        serde_json::to_string_pretty(&self.manifest).unwrap()
+    }
+
+    pub fn write(&self, batch_path: &str, manifest_path: &str) -> io::Result<()> {
+        fs::write(batch_path, &self.text)?;
+        fs::write(manifest_path, self.manifest_json())
+    }
+}
+
+fn valid_value(key: &str, rng: &mut StdRng) -> String {
+    match key {
+        "byr" => rng.gen_range(1920..=2002).to_string(),
+        "iyr" => rng.gen_range(2010..=2020).to_string(),
+        "eyr" => rng.gen_range(2020..=2030).to_string(),
+        "hgt" => {
+            if rng.gen_bool(0.5) {
+                format!("{}cm", rng.gen_range(150..=193))
+            } else {
+                format!("{}in", rng.gen_range(59..=76))
+            }
+        }
+        "hcl" => format!("#{:06x}", rng.gen_range(0..=0xffffff)),
+        "ecl" => EYE_COLORS.choose(rng).unwrap().to_string(),
+        "pid" => format!("{:09}", rng.gen_range(0..1_000_000_000)),
+        "cid" => rng.gen_range(1..=350).to_string(),
+        _ => unreachable!(),
+    }
+}
+
+fn out_of_range_value(key: &str, rng: &mut StdRng) -> String {
+    let pick = |rng: &mut StdRng, low: (i32, i32), high: (i32, i32)| {
+        if rng.gen_bool(0.5) {
+            rng.gen_range(low.0..=low.1)
+        } else {
+            rng.gen_range(high.0..=high.1)
+        }
+    };
+
+    match key {
+        "byr" => pick(rng, (1000, 1919), (2003, 9999)).to_string(),
+        "iyr" => pick(rng, (1000, 2009), (2021, 9999)).to_string(),
+        "eyr" => pick(rng, (1000, 2019), (2031, 9999)).to_string(),
+        "hgt" => {
+            if rng.gen_bool(0.5) {
+                format!("{}cm", pick(rng, (1, 149), (194, 999)))
+            } else {
+                format!("{}in", pick(rng, (1, 58), (77, 999)))
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn malformed_unit_value(rng: &mut StdRng) -> String {
+    let number = rng.gen_range(59..=193);
+    match rng.gen_range(0..4) {
+        0 => number.to_string(),
+        1 => format!("{}mm", number),
+        2 => format!("{}CM", number),
+        _ => format!("cm{}", number),
+    }
+}
+
+fn malformed_value(key: &str, rng: &mut StdRng) -> String {
+    match key {
+        "byr" | "iyr" | "eyr" => format!("{}", rng.gen_range(10..=999)),
+        "hcl" => match rng.gen_range(0..3) {
+            0 => format!("{:06x}", rng.gen_range(0..=0xffffff)),
+            1 => format!("#{:05x}", rng.gen_range(0..=0xfffff)),
+            _ => format!("#{:03x}xyz", rng.gen_range(0..=0xfff)),
+        },
+        "ecl" => ["zzz", "gmt", "blue", "am", "#123abc"].choose(rng).unwrap().to_string(),
+        "pid" => {
+            if rng.gen_bool(0.5) {
+                format!("{:08}", rng.gen_range(0..100_000_000))
+            } else {
+                format!("{:010}", rng.gen_range(0..10_000_000_000u64))
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn layout_record(tokens: &[String], odd_wrapping: bool, rng: &mut StdRng) -> String {
+    if !odd_wrapping {
+        return tokens.join(" ");
+    }
+
+    // Random line breaks, doubled spaces and trailing whitespace, but never a line
+    // that's entirely whitespace, since that would split the record in two.
+    let mut record = String::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        if idx > 0 {
+            match rng.gen_range(0..4) {
+                0 => record.push_str(" \n"),
+                1 => record.push('\n'),
+                2 => record.push_str("  "),
+                _ => record.push(' '),
+            }
+        }
+        if idx == 0 && rng.gen_bool(0.3) {
+            record.push(' ');
+        }
+        record.push_str(token);
+    }
+    record
+}
+
+pub fn generate_batch(config: &GeneratorConfig) -> Result<GeneratedBatch, String> {
+    let kinds = [
+        (RecordKind::Valid, config.valid_weight),
+        (RecordKind::MissingField, config.missing_field_weight),
+        (RecordKind::OutOfRange, config.out_of_range_weight),
+        (RecordKind::MalformedUnit, config.malformed_unit_weight),
+        (RecordKind::MalformedValue, config.malformed_value_weight),
+    ];
+    match kinds.iter().try_fold(0u32, |total, (_, weight)| total.checked_add(*weight)) {
+        Some(0) => return Err("At least one kind of record needs a weight above 0".to_string()),
+        None => return Err("The weights add up to more than a u32 holds".to_string()),
+        Some(_) => {}
+    }
+    for (name, chance) in [("extra key", config.extra_key_chance), ("odd wrapping", config.odd_wrapping_chance)] {
+        if !(0.0..=1.0).contains(&chance) {
+            return Err(format!("The {} chance has to be between 0 and 1, not {}", name, chance));
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let mut records = Vec::new();
+    let mut manifest = Vec::new();
+
+    for index in 0..config.count {
+        let kind = kinds.choose_weighted(&mut rng, |(_, weight)| *weight).unwrap().0;
+
+        let mut fields: Vec<(String, String)> = REQUIRED_KEYS.iter().map(|key| (key.to_string(), valid_value(key, &mut rng))).collect();
+        if rng.gen_bool(0.5) {
+            fields.push(("cid".to_string(), valid_value("cid", &mut rng)));
+        }
+
+        let broken_field = match kind {
+            RecordKind::Valid => None,
+            RecordKind::MissingField => {
+                let idx = rng.gen_range(0..REQUIRED_KEYS.len());
+                Some(fields.remove(idx).0)
+            }
+            RecordKind::OutOfRange => {
+                let key = *["byr", "iyr", "eyr", "hgt"].choose(&mut rng).unwrap();
+                fields.iter_mut().find(|(field_key, _)| field_key == key).unwrap().1 = out_of_range_value(key, &mut rng);
+                Some(key.to_string())
+            }
+            RecordKind::MalformedUnit => {
+                fields.iter_mut().find(|(field_key, _)| field_key == "hgt").unwrap().1 = malformed_unit_value(&mut rng);
+                Some("hgt".to_string())
+            }
+            RecordKind::MalformedValue => {
+                let key = *["byr", "iyr", "eyr", "hcl", "ecl", "pid"].choose(&mut rng).unwrap();
+                fields.iter_mut().find(|(field_key, _)| field_key == key).unwrap().1 = malformed_value(key, &mut rng);
+                Some(key.to_string())
+            }
+        };
+
+        if rng.gen_bool(config.extra_key_chance) {
+            for _ in 0..rng.gen_range(1..=3) {
+                let key = ["zzz", "nam", "dob", "xyz"].choose(&mut rng).unwrap().to_string();
+                if !fields.iter().any(|(field_key, _)| *field_key == key) {
+                    fields.push((key, format!("{}", rng.gen_range(0..1000))));
+                }
+            }
+        }
+
+        fields.shuffle(&mut rng);
+        let tokens: Vec<String> = fields.iter().map(|(key, value)| format!("{}:{}", key, value)).collect();
+        records.push(layout_record(&tokens, rng.gen_bool(config.odd_wrapping_chance), &mut rng));
+
+        manifest.push(ExpectedRecord {
+            index,
+            kind,
+            field: broken_field,
+            valid_star1: kind != RecordKind::MissingField,
+            valid_star2: kind == RecordKind::Valid,
+        });
+    }
+
+    Ok(GeneratedBatch { text: records.join("\n\n") + "\n", manifest })
+}
+
+#[cfg(test)]
+mod generatortests {
+    use super::*;
+    use crate::day4::{convert_processed_line, formats, preprocess_strip_blank_lines};
+
+    fn lines_of(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn generation_is_repeatable() {
+        let config = GeneratorConfig::new(50, 2020);
+
+        assert_eq!(generate_batch(&config).unwrap().text, generate_batch(&config).unwrap().text);
+        assert_ne!(generate_batch(&config).unwrap().text, generate_batch(&GeneratorConfig::new(50, 2021)).unwrap().text);
+    }
+
+    #[test]
+    fn batch_has_the_requested_mix() {
+        let batch = generate_batch(&GeneratorConfig::new(1000, 4)).unwrap();
+
+        assert_eq!(batch.manifest.len(), 1000);
+        for kind in [RecordKind::Valid, RecordKind::MissingField, RecordKind::OutOfRange, RecordKind::MalformedUnit, RecordKind::MalformedValue].iter() {
+            assert!(batch.manifest.iter().any(|record| record.kind == *kind));
+        }
+
+        let mut config = GeneratorConfig::new(100, 4);
+        config.missing_field_weight = 0;
+        config.out_of_range_weight = 0;
+        config.malformed_unit_weight = 0;
+        config.malformed_value_weight = 0;
+        assert!(generate_batch(&config).unwrap().manifest.iter().all(|record| record.valid_star2));
+    }
+
+    #[test]
+    fn bad_configs_are_refused() {
+        let mut config = GeneratorConfig::new(10, 1);
+        config.valid_weight = 0;
+        config.missing_field_weight = 0;
+        config.out_of_range_weight = 0;
+        config.malformed_unit_weight = 0;
+        config.malformed_value_weight = 0;
+        assert!(generate_batch(&config).is_err());
+
+        let mut config = GeneratorConfig::new(10, 1);
+        config.valid_weight = u32::MAX;
+        assert!(generate_batch(&config).is_err());
+
+        let mut config = GeneratorConfig::new(10, 1);
+        config.extra_key_chance = 1.5;
+        assert_eq!(generate_batch(&config).err(), Some("The extra key chance has to be between 0 and 1, not 1.5".to_string()));
+
+        let mut config = GeneratorConfig::new(10, 1);
+        config.odd_wrapping_chance = -0.1;
+        assert!(generate_batch(&config).is_err());
+
+        let mut config = GeneratorConfig::new(10, 1);
+        config.odd_wrapping_chance = f64::NAN;
+        assert!(generate_batch(&config).is_err());
+    }
+
+    #[test]
+    fn validation_matches_manifest() {
+        for seed in 0..5 {
+            let batch = generate_batch(&GeneratorConfig::new(400, seed)).unwrap();
+            let processed_lines = preprocess_strip_blank_lines(&lines_of(&batch.text));
+
+            assert_eq!(processed_lines.len(), batch.manifest.len());
+            for (line, expected) in processed_lines.iter().zip(batch.manifest.iter()) {
+                assert_eq!(convert_processed_line(line, false).is_some(), expected.valid_star1, "star 1, record {}: {}", expected.index, line);
+                assert_eq!(convert_processed_line(line, true).is_some(), expected.valid_star2, "star 2, record {}: {}", expected.index, line);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_parser_agrees_with_preprocessing() {
+        let batch = generate_batch(&GeneratorConfig::new(200, 11)).unwrap();
+        let parsed = formats::parse_batch(&batch.text);
+
+        assert_eq!(parsed.passports.len(), batch.manifest.len());
+        for (passport, expected) in parsed.passports.iter().zip(batch.manifest.iter()) {
+            assert_eq!(passport.is_valid_star2(), expected.valid_star2);
+        }
+    }
+
+    #[test]
+    fn manifest_serialises() {
+        let batch = generate_batch(&GeneratorConfig::new(3, 1)).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&batch.manifest_json()).unwrap();
+
+        assert_eq!(manifest.as_array().unwrap().len(), 3);
+        assert!(manifest[0]["valid_star1"].is_boolean());
+    }
+}
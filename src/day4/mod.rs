@@ -3,7 +3,14 @@
 use super::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use regex::Regex;
+use cursive::views::LinearLayout;
+
+pub mod fields;
+pub mod formats;
+pub mod generator;
+pub mod schema;
+use fields::{EyeColor, Field, HairColor, Height, PassportId};
+use schema::{FieldValue, PassportSchema, Violation};
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -19,6 +26,9 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Passport Inspector 🔎", 2);
+        view.add_item("Export Passports 📤", 3);
+        view.add_item("Generate Test Batch 🧪", 4);
         view.add_item("Back", 999);
     });
 }
@@ -31,6 +41,15 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            passport_inspector(s);
+        }
+        3 => {
+            export_passports(s);
+        }
+        4 => {
+            generate_test_batch(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -52,12 +71,12 @@ pub fn first_star(s: &mut Cursive) {
             
             let valid_passport_count = processed_lines.iter().filter(|line| convert_processed_line(line, false).is_some()).count();
             
-            Ok(format!("Passports scanned: {}\nPassports valid: {}", processed_lines.len(), valid_passport_count))
+            Ok(format!("Passports scanned: {}\nPassports valid: {}\n\n{}", processed_lines.len(), valid_passport_count, describe_failure_reasons(&processed_lines, PassportSchema::star1())))
         },
-        TextView::new,
+        |text| ScrollView::new(TextView::new(text)),
     )
     .with_height(15)
-    .with_width(30);
+    .with_width(40);
 
     s.add_layer(
         Dialog::around(async_view)
@@ -80,12 +99,12 @@ pub fn second_star(s: &mut Cursive) {
             
             let valid_passport_count = processed_lines.iter().filter(|line| convert_processed_line(line, true).is_some()).count();
             
-            Ok(format!("Passports scanned: {}\nPassports valid: {}", processed_lines.len(), valid_passport_count))
+            Ok(format!("Passports scanned: {}\nPassports valid: {}\n\n{}", processed_lines.len(), valid_passport_count, describe_failure_reasons(&processed_lines, PassportSchema::star2())))
         },
-        TextView::new,
+        |text| ScrollView::new(TextView::new(text)),
     )
     .with_height(15)
-    .with_width(30);
+    .with_width(40);
 
     s.add_layer(
         Dialog::around(async_view)
@@ -96,20 +115,133 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
+pub fn passport_inspector(s: &mut Cursive) {
+    let bufreader = BufReader::new(File::open("inputs/day4_1.txt").unwrap());
+    let unprocessed_lines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+    let processed_lines = preprocess_strip_blank_lines(&unprocessed_lines);
+
+    let schema = PassportSchema::star2();
+    let passports: Vec<Passport> = processed_lines.iter().map(|line| parse_processed_line(line)).collect();
+    let first_details = passports.first().map_or(String::new(), |passport| describe_passport_fields(passport, schema));
+
+    let mut passport_list = SelectView::<usize>::new();
+    for (idx, passport) in passports.iter().enumerate() {
+        let marker = if schema.is_valid(passport) { "✅" } else { "❌" };
+        passport_list.add_item(format!("#{:<4} {}", idx + 1, marker), idx);
+    }
+
+    let passport_list = passport_list.on_select(move |s, idx| {
+        let details = describe_passport_fields(&passports[*idx], PassportSchema::star2());
+        s.call_on_name("passport_details", |view: &mut TextView| view.set_content(details));
+    });
+
+    let layout = LinearLayout::horizontal()
+        .child(ScrollView::new(passport_list).fixed_size((12, 20)))
+        .child(TextView::new(first_details).with_name("passport_details").fixed_size((40, 20)));
+
+    s.add_layer(
+        Dialog::around(layout)
+            .title("Passport Inspector 🔎")
+            .button("Done", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn export_passports(s: &mut Cursive) {
+    let batch = formats::parse_batch(&std::fs::read_to_string("inputs/day4_1.txt").unwrap());
+
+    let result = std::fs::create_dir_all("out")
+        .and_then(|_| std::fs::write("out/day4.json", formats::to_json(&batch.passports)))
+        .and_then(|_| std::fs::write("out/day4.csv", formats::to_csv(&batch.passports)));
+
+    let message = match result {
+        Ok(_) => format!("Exported {} passports to out/day4.json and out/day4.csv", batch.passports.len()),
+        Err(err) => format!("Couldn't export passports: {}", err),
+    };
+
+    s.add_layer(
+        Dialog::around(TextView::new(message))
+            .title("Export Passports 📤")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn generate_test_batch(s: &mut Cursive) {
+    let result = generator::generate_batch(&generator::GeneratorConfig::new(1000, 2020)).and_then(|batch| {
+        std::fs::create_dir_all("out")
+            .and_then(|_| batch.write("out/day4_generated.txt", "out/day4_generated_manifest.json"))
+            .map(|_| batch)
+            .map_err(|err| format!("Couldn't write the batch: {}", err))
+    });
+
+    let message = match result {
+        Ok(batch) => format!(
+            "Wrote {} passports ({} valid for the 2nd star) to out/day4_generated.txt",
+            batch.manifest.len(),
+            batch.manifest.iter().filter(|record| record.valid_star2).count()
+        ),
+        Err(message) => message,
+    };
+
+    s.add_layer(
+        Dialog::around(TextView::new(message))
+            .title("Generate Test Batch 🧪")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn describe_failure_reasons(processed_lines: &[String], schema: &PassportSchema) -> String {
+    let violations: Vec<Vec<Violation>> = processed_lines.iter().map(|line| schema.validate(&parse_processed_line(line))).collect();
+    let tally = schema::tally_failure_reasons(&violations);
+
+    if tally.is_empty() {
+        return "No failures!".to_string();
+    }
+
+    let mut description = "Failures:".to_string();
+    for (reason, count) in tally {
+        description.push_str(&format!("\n  {:>4} x {}", count, reason));
+    }
+    description
+}
+
+pub fn describe_passport_fields(passport: &Passport, schema: &PassportSchema) -> String {
+    let mut description = String::new();
+
+    for field in schema.report(passport) {
+        let marker = if field.passed() { "✅" } else { "❌" };
+        description.push_str(&format!("{} {}: {}\n", marker, field.field, field.value.as_deref().unwrap_or("-")));
+        for violation in &field.violations {
+            description.push_str(&format!("     {}\n", violation));
+        }
+    }
+
+    description
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Passport {
-    byr: Option<String>,
-    iyr: Option<String>,
-    eyr: Option<String>,
-    hgt: Option<String>,
-    hcl: Option<String>,
-    ecl: Option<String>,
-    pid: Option<String>,
-    cid: Option<String>,
-    star2_format: bool
+    pub byr: Option<Field<i32>>,
+    pub iyr: Option<Field<i32>>,
+    pub eyr: Option<Field<i32>>,
+    pub hgt: Option<Field<Height>>,
+    pub hcl: Option<Field<HairColor>>,
+    pub ecl: Option<Field<EyeColor>>,
+    pub pid: Option<Field<PassportId>>,
+    pub cid: Option<String>,
+    // Keys we don't model, in the order they were read.
+    pub extra: Vec<(String, String)>,
+    // Every key in the order it was read, and whether a line break followed it.
+    layout: Vec<(String, bool)>,
 }
 
 impl Passport {
-    fn new() -> Passport {
+    pub fn new() -> Passport {
         Passport {
             byr: None,
             iyr: None,
@@ -119,110 +251,88 @@ impl Passport {
             ecl: None,
             pid: None,
             cid: None,
-            star2_format: false
+            extra: Vec::new(),
+            layout: Vec::new(),
             }
     }
 
-    fn is_valid(&self) -> bool {
-        match self.star2_format {
-            true => self.is_valid_star2(),
-            false => self.is_valid_star1()
-        }
+    pub fn is_valid_star1(&self) -> bool {
+        PassportSchema::star1().is_valid(self)
     }
 
-    fn is_valid_star1(&self) -> bool {
-        self.byr.is_some() &&
-        self.iyr.is_some() &&
-        self.eyr.is_some() &&
-        self.hgt.is_some() &&
-        self.hcl.is_some() &&
-        self.ecl.is_some() &&
-        self.pid.is_some()
+    pub fn is_valid_star2(&self) -> bool {
+        PassportSchema::star2().is_valid(self)
     }
 
-    fn is_valid_star2(&self) -> bool {
-        if !self.is_valid_star1() {
-            return false;
+    pub fn get_field(&self, key: &str) -> Option<&str> {
+        fn raw<T>(field: &Option<Field<T>>) -> Option<&str> {
+            field.as_ref().map(|field| field.raw())
         }
 
-        let mut valid = true;      
-        
-        // Birth year is a 4 digit number between 1920 and 2002.
-        valid &= self.byr.as_ref().unwrap().len() == 4;
-        if self.byr.as_ref().unwrap().parse::<i32>().is_ok() {
-            let byr = self.byr.as_ref().unwrap().parse::<i32>().unwrap();
-            valid &= byr >= 1920 && byr <= 2002;
-        }
-        else {
-            valid = false;
+        match key {
+            "byr" => raw(&self.byr),
+            "iyr" => raw(&self.iyr),
+            "eyr" => raw(&self.eyr),
+            "hgt" => raw(&self.hgt),
+            "hcl" => raw(&self.hcl),
+            "ecl" => raw(&self.ecl),
+            "pid" => raw(&self.pid),
+            "cid" => self.cid.as_deref(),
+            _ => self.extra.iter().find(|(extra_key, _)| extra_key == key).map(|(_, value)| value.as_str()),
         }
+    }
 
-
-        // Issue year is a 4 digit number between 2010 and 2020.
-        valid &= self.iyr.as_ref().unwrap().len() == 4;
-        if self.iyr.as_ref().unwrap().parse::<i32>().is_ok() {
-            let iyr = self.iyr.as_ref().unwrap().parse::<i32>().unwrap();
-            valid &= iyr >= 2010 && iyr <= 2020;
-        }
-        else {
-            valid = false;
+    // The field as the schema checks it, with the typed number for the keys the model has one for.
+    pub fn field_value(&self, key: &str) -> Option<FieldValue<'_>> {
+        match key {
+            "byr" => self.byr.as_ref().map(FieldValue::from_field),
+            "iyr" => self.iyr.as_ref().map(FieldValue::from_field),
+            "eyr" => self.eyr.as_ref().map(FieldValue::from_field),
+            "hgt" => self.hgt.as_ref().map(FieldValue::from_field),
+            _ => self.get_field(key).map(FieldValue::from_text),
         }
+    }
 
-        // Expiration year is a 4 digit number between 2020 and 2030.
-        valid &= self.eyr.as_ref().unwrap().len() == 4;
-        if self.eyr.as_ref().unwrap().parse::<i32>().is_ok() {
-            let eyr = self.eyr.as_ref().unwrap().parse::<i32>().unwrap();
-            valid &= eyr >= 2020 && eyr <= 2030;
-        }
-        else {
-            valid = false;
+    pub fn set_field(&mut self, key: &str, value: &str) {
+        match key {
+            "byr" => self.byr = Some(Field::parse(value)),
+            "iyr" => self.iyr = Some(Field::parse(value)),
+            "eyr" => self.eyr = Some(Field::parse(value)),
+            "hgt" => self.hgt = Some(Field::parse(value)),
+            "hcl" => self.hcl = Some(Field::parse(value)),
+            "ecl" => self.ecl = Some(Field::parse(value)),
+            "pid" => self.pid = Some(Field::parse(value)),
+            "cid" => self.cid = Some(value.to_string()),
+            _ => match self.extra.iter_mut().find(|(extra_key, _)| extra_key == key) {
+                Some(extra) => extra.1 = value.to_string(),
+                None => self.extra.push((key.to_string(), value.to_string())),
+            },
         }
 
-        // Height is a number followed by "cm" or "in".
-        let height_pattern = Regex::new(r"([0-9]+)(cm|in)").unwrap();
-        let height_caps = height_pattern.captures(self.hgt.as_ref().unwrap());
-        if height_caps.is_some() {
-            let height_num = height_caps.as_ref().unwrap().get(1);
-            let height_unit = height_caps.as_ref().unwrap().get(2);
-            
-            valid &= height_num.is_some() && height_unit.is_some();
-            if valid {
-                valid &= height_num.as_ref().unwrap().as_str().parse::<i32>().is_ok();
-                if valid {
-                    let height_numeric = height_num.as_ref().unwrap().as_str().parse::<i32>().unwrap();
-
-                    match height_unit.unwrap().as_str() {
-                        "cm" => {
-                            valid &= height_numeric >= 150 && height_numeric <= 193;
-                        },
-                        "in" => {
-                            valid &= height_numeric >= 59 && height_numeric <= 76;
-                        },
-                        _ => { valid = false; }
-                    }
-                }
-            }
+        if !self.layout.iter().any(|(layout_key, _)| layout_key == key) {
+            self.layout.push((key.to_string(), false));
         }
-        else {
-            valid = false;
-        }
-
-        // Hair color is a # followed by six characters 0-9 or a-f
-        let hair_pattern = Regex::new(r"#([0-9 a-f]{6})").unwrap();
-        valid &= self.hcl.as_ref().unwrap().len() == 7;
-        valid &= hair_pattern.is_match(self.hcl.as_ref().unwrap());
+    }
 
-        // Eye color is exactly one of: amb blu brn gry grn hzl oth
-        let eye_pattern = Regex::new(r"(amb|blu|brn|gry|grn|hzl|oth)").unwrap();
-        valid &= eye_pattern.is_match(self.ecl.as_ref().unwrap());
-        valid &= eye_pattern.find_iter(self.ecl.as_ref().unwrap()).count() == 1;
+    pub fn mark_line_break(&mut self) {
+        if let Some(last) = self.layout.last_mut() {
+            last.1 = true;
+        }
+    }
 
-        // Passport id is a 9-digit number.
-        let pid_pattern = Regex::new(r"[0-9]{9}").unwrap();
-        valid &= self.pid.as_ref().unwrap().len() == 9;
-        valid &= pid_pattern.is_match(self.pid.as_ref().unwrap());
+    // Every key that has a value, with its raw text and whether a line break follows it.
+    // Keys keep the order they were read in; anything set later goes on the end.
+    pub fn entries(&self) -> Vec<(&str, &str, bool)> {
+        self.layout
+            .iter()
+            .filter_map(|(key, line_break)| self.get_field(key).map(|value| (key.as_str(), value, *line_break)))
+            .collect()
+    }
+}
 
-        valid
+impl Default for Passport {
+    fn default() -> Passport {
+        Passport::new()
     }
 }
 
@@ -255,34 +365,23 @@ pub fn preprocess_strip_blank_lines(lines: &Vec<String>) -> Vec<String> {
 }
 
 pub fn convert_processed_line(line: &String, star2_format: bool) -> Option<Passport> {
+    let passport = parse_processed_line(line);
 
-    let mut passport = Passport::new();
-    passport.star2_format = star2_format;
-    let key_pairs: Vec<&str> = line.split(' ').collect();
-    key_pairs.iter().for_each(|kvp| {
-        let mut split_iter = kvp.split(':');
-        let key = split_iter.next().unwrap();
-        let value = split_iter.next().unwrap();
+    let valid = match star2_format {
+        true => passport.is_valid_star2(),
+        false => passport.is_valid_star1()
+    };
 
-        match key {
-            "byr" => passport.byr = Some(value.to_string()),
-            "iyr" => passport.iyr = Some(value.to_string()),
-            "eyr" => passport.eyr = Some(value.to_string()),
-            "hgt" => passport.hgt = Some(value.to_string()),
-            "hcl" => passport.hcl = Some(value.to_string()),
-            "ecl" => passport.ecl = Some(value.to_string()),
-            "pid" => passport.pid = Some(value.to_string()),
-            "cid" => passport.cid = Some(value.to_string()),
-            _=> {}
-        }
-    });
-
-    match passport.is_valid() {
+    match valid {
         true => Some(passport),
         false => None
     }
 }
 
+pub fn parse_processed_line(line: &str) -> Passport {
+    formats::parse_record(line)
+}
+
 
 #[cfg(test)]
 mod day4tests {
@@ -315,32 +414,32 @@ mod day4tests {
     fn passport_validation_works_star1() {
         let invalid_passport = Passport::new();
         let valid_passport = Passport { 
-            byr: Some("a".to_string()),
-            iyr: Some("a".to_string()),
-            eyr: Some("a".to_string()),
-            hgt: Some("a".to_string()),
-            hcl: Some("a".to_string()),
-            ecl: Some("a".to_string()),
-            pid: Some("a".to_string()),
+            byr: Some(Field::parse("a")),
+            iyr: Some(Field::parse("a")),
+            eyr: Some(Field::parse("a")),
+            hgt: Some(Field::parse("a")),
+            hcl: Some(Field::parse("a")),
+            ecl: Some(Field::parse("a")),
+            pid: Some(Field::parse("a")),
             cid: Some("a".to_string()),
-            star2_format: false
+            ..Passport::new()
         };
         let valid_north_pole_id = Passport {
-            byr: Some("a".to_string()),
-            iyr: Some("a".to_string()),
-            eyr: Some("a".to_string()),
-            hgt: Some("a".to_string()),
-            hcl: Some("a".to_string()),
-            ecl: Some("🎄".to_string()),
-            pid: Some("a".to_string()),
+            byr: Some(Field::parse("a")),
+            iyr: Some(Field::parse("a")),
+            eyr: Some(Field::parse("a")),
+            hgt: Some(Field::parse("a")),
+            hcl: Some(Field::parse("a")),
+            ecl: Some(Field::parse("🎄")),
+            pid: Some(Field::parse("a")),
             cid: None,
-            star2_format: false
+            ..Passport::new()
         };
 
 
-        assert_eq!(invalid_passport.is_valid(), false);
-        assert_eq!(valid_passport.is_valid(), true);
-        assert_eq!(valid_north_pole_id.is_valid(), true);
+        assert_eq!(invalid_passport.is_valid_star1(), false);
+        assert_eq!(valid_passport.is_valid_star1(), true);
+        assert_eq!(valid_north_pole_id.is_valid_star1(), true);
     }
 
     #[test]
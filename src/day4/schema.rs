@@ -0,0 +1,469 @@
+// Day 4: Passport Processing - Validation Schemas
+// Passport rules live in TOML files rather than code. Both stars ship as bundled schemas.
+use super::fields::{Field, Measured};
+use super::Passport;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+const STAR1_SCHEMA: &str = include_str!("../../config/day4_star1.toml");
+const STAR2_SCHEMA: &str = include_str!("../../config/day4_star2.toml");
+
+#[derive(Deserialize)]
+struct SchemaFile {
+    fields: BTreeMap<String, FieldRuleFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldRuleFile {
+    #[serde(default)]
+    required: bool,
+    pattern: Option<String>,
+    digits: Option<usize>,
+    min: Option<i64>,
+    max: Option<i64>,
+    #[serde(default)]
+    units: BTreeMap<String, RangeFile>,
+    one_of: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangeFile {
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntRange {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+impl IntRange {
+    pub fn contains(&self, value: i64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+pub struct FieldRule {
+    pub name: String,
+    pub required: bool,
+    // Compiled once, anchored at both ends.
+    pub pattern: Option<Regex>,
+    pub digits: Option<usize>,
+    pub range: Option<IntRange>,
+    pub units: Vec<(String, IntRange)>,
+    pub one_of: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ViolationKind {
+    Missing,
+    PatternMismatch { value: String },
+    NotANumber { value: String },
+    WrongDigitCount { found: usize, expected: usize },
+    BelowMin { value: i64, min: i64 },
+    AboveMax { value: i64, max: i64 },
+    UnknownUnit { value: String, unit: String },
+    NotAllowed { value: String },
+}
+
+impl ViolationKind {
+    // Short description without the offending value, so violations can be grouped.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ViolationKind::Missing => "missing",
+            ViolationKind::PatternMismatch { .. } => "bad format",
+            ViolationKind::NotANumber { .. } => "not a number",
+            ViolationKind::WrongDigitCount { .. } => "wrong number of digits",
+            ViolationKind::BelowMin { .. } => "below min",
+            ViolationKind::AboveMax { .. } => "above max",
+            ViolationKind::UnknownUnit { .. } => "unknown unit",
+            ViolationKind::NotAllowed { .. } => "not an allowed value",
+        }
+    }
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViolationKind::Missing => write!(f, "missing"),
+            ViolationKind::PatternMismatch { value } => write!(f, "{} has a bad format", value),
+            ViolationKind::NotANumber { value } => write!(f, "{} is not a number", value),
+            ViolationKind::WrongDigitCount { found, expected } => write!(f, "{} digits, expected {}", found, expected),
+            ViolationKind::BelowMin { value, min } => write!(f, "{} below min {}", value, min),
+            ViolationKind::AboveMax { value, max } => write!(f, "{} above max {}", value, max),
+            ViolationKind::UnknownUnit { value, unit } => write!(f, "{} has unknown unit '{}'", value, unit),
+            ViolationKind::NotAllowed { value } => write!(f, "{} is not an allowed value", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub field: String,
+    pub kind: ViolationKind,
+}
+
+impl Violation {
+    pub fn reason(&self) -> String {
+        format!("{}: {}", self.field, self.kind.reason())
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.kind)
+    }
+}
+
+fn check_range(range: &IntRange, value: i64, violations: &mut Vec<ViolationKind>) {
+    if let Some(min) = range.min {
+        if value < min {
+            violations.push(ViolationKind::BelowMin { value, min });
+        }
+    }
+    if let Some(max) = range.max {
+        if value > max {
+            violations.push(ViolationKind::AboveMax { value, max });
+        }
+    }
+}
+
+// What a rule sees of a field. Formats are checked against the text as it was read, and
+// ranges and units against the typed value from the passport model. Where there's no typed
+// value, because the key isn't modelled or the text didn't parse, the number and unit are
+// read from the text instead so the violation can still say what was wrong.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldValue<'a> {
+    raw: &'a str,
+    measure: Option<(i64, &'a str)>,
+}
+
+impl<'a> FieldValue<'a> {
+    pub fn from_text(raw: &'a str) -> FieldValue<'a> {
+        FieldValue { raw, measure: split_number_and_unit(raw) }
+    }
+
+    pub fn from_field<T: Measured>(field: &'a Field<T>) -> FieldValue<'a> {
+        FieldValue { raw: field.raw(), measure: field.value().map(Measured::measure).or_else(|| split_number_and_unit(field.raw())) }
+    }
+
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    pub fn measure(&self) -> Option<(i64, &'a str)> {
+        self.measure
+    }
+}
+
+impl FieldRule {
+    pub fn check(&self, value: Option<&str>) -> bool {
+        self.validate(value).is_empty()
+    }
+
+    pub fn validate(&self, value: Option<&str>) -> Vec<ViolationKind> {
+        self.validate_value(value.map(FieldValue::from_text))
+    }
+
+    pub fn validate_value(&self, field: Option<FieldValue>) -> Vec<ViolationKind> {
+        let mut violations = Vec::new();
+
+        let (value, measure) = match field {
+            Some(field) => (field.raw, field.measure),
+            None => {
+                if self.required {
+                    violations.push(ViolationKind::Missing);
+                }
+                return violations;
+            }
+        };
+
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                violations.push(ViolationKind::PatternMismatch { value: value.to_string() });
+            }
+        }
+
+        if let Some(expected) = self.digits {
+            if value.is_empty() || !value.chars().all(|chr| chr.is_ascii_digit()) {
+                violations.push(ViolationKind::NotANumber { value: value.to_string() });
+            } else if value.len() != expected {
+                violations.push(ViolationKind::WrongDigitCount { found: value.len(), expected });
+            }
+        }
+
+        if let Some(range) = &self.range {
+            match measure {
+                Some((number, "")) => check_range(range, number, &mut violations),
+                _ => {
+                    // The digits rule already complained about this one.
+                    if !violations.iter().any(|kind| matches!(kind, ViolationKind::NotANumber { .. })) {
+                        violations.push(ViolationKind::NotANumber { value: value.to_string() });
+                    }
+                }
+            }
+        }
+
+        if !self.units.is_empty() {
+            match measure {
+                Some((number, unit)) => match self.units.iter().find(|(name, _)| name == unit) {
+                    Some((_, range)) => check_range(range, number, &mut violations),
+                    None => violations.push(ViolationKind::UnknownUnit { value: value.to_string(), unit: unit.to_string() }),
+                },
+                None => violations.push(ViolationKind::NotANumber { value: value.to_string() }),
+            }
+        }
+
+        if let Some(one_of) = &self.one_of {
+            if !one_of.iter().any(|allowed| allowed == value) {
+                violations.push(ViolationKind::NotAllowed { value: value.to_string() });
+            }
+        }
+
+        violations
+    }
+}
+
+pub struct FieldReport {
+    pub field: String,
+    pub value: Option<String>,
+    pub violations: Vec<ViolationKind>,
+}
+
+impl FieldReport {
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+pub fn split_number_and_unit(value: &str) -> Option<(i64, &str)> {
+    let digits_end = value.find(|chr: char| !chr.is_ascii_digit()).unwrap_or(value.len());
+    let number = value[..digits_end].parse().ok()?;
+    Some((number, &value[digits_end..]))
+}
+
+pub struct PassportSchema {
+    pub fields: Vec<FieldRule>,
+}
+
+impl PassportSchema {
+    pub fn load(path: &str) -> Result<PassportSchema, String> {
+        let contents = fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+        PassportSchema::from_toml_str(&contents)
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<PassportSchema, String> {
+        let schema_file: SchemaFile = toml::from_str(contents).map_err(|err| format!("Bad passport schema: {}", err))?;
+
+        let mut fields = Vec::new();
+        for (name, rule) in schema_file.fields {
+            let pattern = match rule.pattern {
+                Some(pattern) => Some(Regex::new(&format!("^(?:{})$", pattern)).map_err(|err| format!("Bad pattern for {}: {}", name, err))?),
+                None => None,
+            };
+
+            let range = if rule.min.is_some() || rule.max.is_some() {
+                Some(IntRange { min: rule.min, max: rule.max })
+            } else {
+                None
+            };
+
+            let units = rule.units.into_iter().map(|(unit, range)| (unit, IntRange { min: range.min, max: range.max })).collect();
+
+            fields.push(FieldRule { name, required: rule.required, pattern, digits: rule.digits, range, units, one_of: rule.one_of });
+        }
+
+        Ok(PassportSchema { fields })
+    }
+
+    pub fn star1() -> &'static PassportSchema {
+        static SCHEMA: OnceLock<PassportSchema> = OnceLock::new();
+        SCHEMA.get_or_init(|| PassportSchema::from_toml_str(STAR1_SCHEMA).unwrap())
+    }
+
+    pub fn star2() -> &'static PassportSchema {
+        static SCHEMA: OnceLock<PassportSchema> = OnceLock::new();
+        SCHEMA.get_or_init(|| PassportSchema::from_toml_str(STAR2_SCHEMA).unwrap())
+    }
+
+    pub fn is_valid(&self, passport: &Passport) -> bool {
+        self.fields.iter().all(|rule| rule.validate_value(passport.field_value(&rule.name)).is_empty())
+    }
+
+    pub fn validate(&self, passport: &Passport) -> Vec<Violation> {
+        self.fields
+            .iter()
+            .flat_map(|rule| {
+                rule.validate_value(passport.field_value(&rule.name))
+                    .into_iter()
+                    .map(move |kind| Violation { field: rule.name.to_string(), kind })
+            })
+            .collect()
+    }
+
+    // One entry per field in the schema, in schema order, whether it passed or not.
+    pub fn report(&self, passport: &Passport) -> Vec<FieldReport> {
+        self.fields
+            .iter()
+            .map(|rule| {
+                let value = passport.field_value(&rule.name);
+                FieldReport {
+                    field: rule.name.to_string(),
+                    value: value.map(|value| value.raw().to_string()),
+                    violations: rule.validate_value(value),
+                }
+            })
+            .collect()
+    }
+}
+
+// Counts how often each failure reason shows up across a batch, most common first.
+pub fn tally_failure_reasons(violations_per_passport: &[Vec<Violation>]) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+
+    for violation in violations_per_passport.iter().flatten() {
+        *counts.entry(violation.reason()).or_insert(0) += 1;
+    }
+
+    let mut tally: Vec<(String, usize)> = counts.into_iter().collect();
+    tally.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tally
+}
+
+#[cfg(test)]
+mod schematests {
+    use super::*;
+    use crate::day4::fields::{Field, Height, HeightUnit};
+    use crate::day4::{convert_processed_line, parse_processed_line};
+
+    #[test]
+    fn bundled_schemas_load() {
+        assert_eq!(PassportSchema::star1().fields.len(), 8);
+        assert_eq!(PassportSchema::star2().fields.len(), 8);
+        assert!(PassportSchema::star1().fields.iter().all(|rule| rule.pattern.is_none()));
+    }
+
+    #[test]
+    fn patterns_are_anchored() {
+        let schema = PassportSchema::star2();
+        let rule_for = |name: &str| schema.fields.iter().find(|rule| rule.name == name).unwrap();
+
+        assert!(rule_for("hcl").check(Some("#123abc")));
+        assert!(!rule_for("hcl").check(Some("#12 abc")));
+        assert!(!rule_for("hcl").check(Some("#123abcd")));
+        assert!(!rule_for("hcl").check(Some("x#123abc")));
+
+        assert!(rule_for("pid").check(Some("000000001")));
+        assert!(!rule_for("pid").check(Some("0123456789")));
+        assert!(!rule_for("pid").check(Some("+00000001")));
+
+        assert!(!rule_for("ecl").check(Some("amb blu")));
+        assert!(!rule_for("ecl").check(Some("ambb")));
+    }
+
+    #[test]
+    fn ranges_and_units_work() {
+        let schema = PassportSchema::star2();
+        let rule_for = |name: &str| schema.fields.iter().find(|rule| rule.name == name).unwrap();
+
+        assert!(rule_for("byr").check(Some("2002")));
+        assert!(!rule_for("byr").check(Some("2003")));
+
+        assert!(rule_for("hgt").check(Some("60in")));
+        assert!(rule_for("hgt").check(Some("190cm")));
+        assert!(!rule_for("hgt").check(Some("190in")));
+        assert!(!rule_for("hgt").check(Some("190")));
+
+        assert!(!rule_for("byr").check(None));
+        assert!(rule_for("cid").check(None));
+    }
+
+    #[test]
+    fn puzzle_examples_validate() {
+        let valid_passports = [
+            "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f".to_string(),
+            "eyr:2029 ecl:blu cid:129 byr:1989 iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm".to_string(),
+            "hcl:#888785 hgt:164cm byr:2001 iyr:2015 cid:88 pid:545766238 ecl:hzl eyr:2022".to_string(),
+            "iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719".to_string(),
+        ];
+
+        assert_eq!(valid_passports.iter().filter(|pass| convert_processed_line(pass, true).is_some()).count(), 4);
+    }
+
+    #[test]
+    fn violations_describe_failures() {
+        let passport = parse_processed_line("hgt:59cm ecl:zzz eyr:2038 hcl:74454a iyr:2023 pid:3556412378 byr:2007");
+        let violations: Vec<String> = PassportSchema::star2().validate(&passport).iter().map(|violation| violation.to_string()).collect();
+
+        assert_eq!(
+            violations,
+            vec![
+                "byr: 2007 above max 2002",
+                "ecl: zzz is not an allowed value",
+                "eyr: 2038 above max 2030",
+                "hcl: 74454a has a bad format",
+                "hgt: 59 below min 150",
+                "iyr: 2023 above max 2020",
+                "pid: 10 digits, expected 9",
+            ]
+        );
+
+        let passport = parse_processed_line("byr:1980 iyr:2012 eyr:2030 hgt:74in ecl:grn pid:087499704");
+        let violations = PassportSchema::star2().validate(&passport);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].to_string(), "hcl: missing");
+    }
+
+    #[test]
+    fn typed_values_are_checked() {
+        // The typed height is what gets range checked, and the report keeps the text it came from.
+        let passport = Passport { hgt: Some(Field::parse("0200cm")), ..Passport::new() };
+        assert_eq!(passport.hgt.as_ref().unwrap().value(), Some(&Height { value: 200, unit: HeightUnit::Centimetres }));
+        let report = PassportSchema::star2().report(&passport);
+        let hgt = report.iter().find(|field| field.field == "hgt").unwrap();
+
+        assert_eq!(hgt.value, Some("0200cm".to_string()));
+        assert_eq!(hgt.violations, vec![ViolationKind::AboveMax { value: 200, max: 193 }]);
+
+        let passport = parse_processed_line("byr:1990 hgt:70in");
+        assert!(PassportSchema::star2().report(&passport).iter().filter(|field| field.field == "byr" || field.field == "hgt").all(|field| field.passed()));
+    }
+
+    #[test]
+    fn reports_cover_every_field() {
+        let passport = parse_processed_line("byr:1980 iyr:2012 eyr:2030 hgt:74 ecl:grn pid:08749970a");
+        let report = PassportSchema::star2().report(&passport);
+
+        assert_eq!(report.len(), 8);
+        assert!(report.iter().find(|field| field.field == "byr").unwrap().passed());
+        assert!(report.iter().find(|field| field.field == "cid").unwrap().passed());
+        assert_eq!(report.iter().find(|field| field.field == "hgt").unwrap().violations, vec![ViolationKind::UnknownUnit { value: "74".to_string(), unit: "".to_string() }]);
+        assert_eq!(report.iter().find(|field| field.field == "pid").unwrap().violations, vec![ViolationKind::NotANumber { value: "08749970a".to_string() }]);
+        assert_eq!(report.iter().find(|field| field.field == "hcl").unwrap().value, None);
+    }
+
+    #[test]
+    fn failure_reasons_are_tallied() {
+        let lines = ["byr:2007", "byr:2008 hcl:#123abc"];
+        let violations: Vec<Vec<Violation>> = lines.iter().map(|line| PassportSchema::star2().validate(&parse_processed_line(line))).collect();
+        let tally = tally_failure_reasons(&violations);
+
+        assert_eq!(tally[0], ("byr: above max".to_string(), 2));
+        assert!(tally.contains(&("hcl: missing".to_string(), 1)));
+        assert!(tally.contains(&("pid: missing".to_string(), 2)));
+    }
+
+    #[test]
+    fn bad_schemas_are_rejected() {
+        assert!(PassportSchema::from_toml_str("[fields.byr]\npattern = \"[0-9\"\n").is_err());
+        assert!(PassportSchema::from_toml_str("[fields.byr]\nrequierd = true\n").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+// Day 5: Binary Boarding - Seating Layouts
+// A boarding pass is a row number in binary followed by a column number in binary,
+// each spelled with its own pair of letters. The layout says how many of each.
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardingPassError {
+    WrongLength { expected: usize, found: usize },
+    InvalidLetter { position: usize, found: char, expected: (char, char) },
+    SeatOutOfRange { row: i32, col: i32 },
+}
+
+impl fmt::Display for BoardingPassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardingPassError::WrongLength { expected, found } => write!(f, "token is {} characters long, expected {}", found, expected),
+            BoardingPassError::InvalidLetter { position, found, expected } => {
+                write!(f, "'{}' at position {} should be {} or {}", found, position + 1, expected.0, expected.1)
+            }
+            BoardingPassError::SeatOutOfRange { row, col } => write!(f, "there's no seat at row {}, column {}", row, col),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeatingLayout {
+    row_bits: u32,
+    col_bits: u32,
+    // (lower half, upper half) for each axis.
+    row_letters: (char, char),
+    col_letters: (char, char),
+}
+
+impl SeatingLayout {
+    pub fn new(row_bits: u32, col_bits: u32, row_letters: (char, char), col_letters: (char, char)) -> Result<SeatingLayout, String> {
+        if row_bits as u64 + col_bits as u64 > 30 {
+            return Err(format!("{} row bits and {} column bits won't fit a seat id", row_bits, col_bits));
+        }
+
+        let letters = [row_letters.0, row_letters.1, col_letters.0, col_letters.1];
+        if (0..letters.len()).any(|idx| letters[idx + 1..].contains(&letters[idx])) {
+            return Err(format!("Every letter needs to be different, got {:?}", letters));
+        }
+
+        Ok(SeatingLayout { row_bits, col_bits, row_letters, col_letters })
+    }
+
+    // 128 rows by 8 columns, as on the puzzle's plane.
+    pub fn standard() -> SeatingLayout {
+        SeatingLayout { row_bits: 7, col_bits: 3, row_letters: ('F', 'B'), col_letters: ('L', 'R') }
+    }
+
+    // 256 rows by 16 columns.
+    pub fn wide_body() -> SeatingLayout {
+        SeatingLayout { row_bits: 8, col_bits: 4, row_letters: ('F', 'B'), col_letters: ('L', 'R') }
+    }
+
+    pub fn row_bits(&self) -> u32 {
+        self.row_bits
+    }
+
+    pub fn col_bits(&self) -> u32 {
+        self.col_bits
+    }
+
+    pub fn row_letters(&self) -> (char, char) {
+        self.row_letters
+    }
+
+    pub fn col_letters(&self) -> (char, char) {
+        self.col_letters
+    }
+
+    pub fn rows(&self) -> i32 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> i32 {
+        1 << self.col_bits
+    }
+
+    pub fn seat_count(&self) -> i32 {
+        self.rows() * self.cols()
+    }
+
+    pub fn token_length(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    // Checks length and letters without decoding anything.
+    pub fn validate(&self, token: &str) -> Result<(), BoardingPassError> {
+        let found = token.chars().count();
+        if found != self.token_length() {
+            return Err(BoardingPassError::WrongLength { expected: self.token_length(), found });
+        }
+
+        for (position, letter) in token.chars().enumerate() {
+            let letters = if position < self.row_bits as usize { self.row_letters } else { self.col_letters };
+            if letter != letters.0 && letter != letters.1 {
+                return Err(BoardingPassError::InvalidLetter { position, found: letter, expected: letters });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn decode(&self, token: &str) -> Result<(i32, i32), BoardingPassError> {
+        self.validate(token)?;
+
+        let mut row = 0;
+        let mut col = 0;
+
+        for (position, letter) in token.chars().enumerate() {
+            let (value, letters) = if position < self.row_bits as usize { (&mut row, self.row_letters) } else { (&mut col, self.col_letters) };
+
+            // Each letter halves the remaining range: the lower half is a 0 bit, the upper a 1.
+            *value <<= 1;
+            if letter == letters.1 {
+                *value |= 1;
+            }
+        }
+
+        Ok((row, col))
+    }
+
+    pub fn seat_id(&self, seat: (i32, i32)) -> i32 {
+        (seat.0 * self.cols()) + seat.1
+    }
+
+    pub fn decode_seat_id(&self, token: &str) -> Result<i32, BoardingPassError> {
+        self.decode(token).map(|seat| self.seat_id(seat))
+    }
+
+    pub fn encode(&self, seat: (i32, i32)) -> Result<String, BoardingPassError> {
+        let (row, col) = seat;
+        if row < 0 || row >= self.rows() || col < 0 || col >= self.cols() {
+            return Err(BoardingPassError::SeatOutOfRange { row, col });
+        }
+
+        let spell = |value: i32, bits: u32, letters: (char, char)| {
+            (0..bits).rev().map(move |bit| if value & (1 << bit) != 0 { letters.1 } else { letters.0 })
+        };
+
+        Ok(spell(row, self.row_bits, self.row_letters).chain(spell(col, self.col_bits, self.col_letters)).collect())
+    }
+
+    pub fn encode_seat_id(&self, seat_id: i32) -> Result<String, BoardingPassError> {
+        if seat_id < 0 || seat_id >= self.seat_count() {
+            return Err(BoardingPassError::SeatOutOfRange { row: seat_id.div_euclid(self.cols()), col: seat_id.rem_euclid(self.cols()) });
+        }
+        self.encode((seat_id / self.cols(), seat_id % self.cols()))
+    }
+}
+
+#[cfg(test)]
+mod layouttests {
+    use super::*;
+
+    #[test]
+    fn standard_layout_matches_puzzle() {
+        let layout = SeatingLayout::standard();
+
+        assert_eq!(layout.decode("FBFBBFFRLR"), Ok((44, 5)));
+        assert_eq!(layout.decode("BFFFBBFRRR"), Ok((70, 7)));
+        assert_eq!(layout.decode_seat_id("FFFBBBFRRR"), Ok(119));
+        assert_eq!(layout.decode_seat_id("BBFFBBFRLL"), Ok(820));
+        assert_eq!(layout.seat_count(), 1024);
+    }
+
+    #[test]
+    fn wide_body_layout_works() {
+        let layout = SeatingLayout::wide_body();
+
+        assert_eq!(layout.rows(), 256);
+        assert_eq!(layout.cols(), 16);
+        assert_eq!(layout.decode("BBBBBBBBRRRR"), Ok((255, 15)));
+        assert_eq!(layout.decode("FFFFFFFBLLLR"), Ok((1, 1)));
+        assert_eq!(layout.decode_seat_id("FFFFFFFBLLLR"), Ok(17));
+    }
+
+    #[test]
+    fn custom_letters_work() {
+        let layout = SeatingLayout::new(2, 2, ('U', 'D'), ('P', 'S')).unwrap();
+
+        assert_eq!((layout.row_bits(), layout.col_bits()), (2, 2));
+        assert_eq!((layout.row_letters(), layout.col_letters()), (('U', 'D'), ('P', 'S')));
+        assert_eq!(layout.decode("DUSP"), Ok((2, 2)));
+        assert_eq!(layout.decode("FBLR"), Err(BoardingPassError::InvalidLetter { position: 0, found: 'F', expected: ('U', 'D') }));
+
+        assert!(SeatingLayout::new(2, 2, ('F', 'B'), ('B', 'R')).is_err());
+        assert!(SeatingLayout::new(20, 20, ('F', 'B'), ('L', 'R')).is_err());
+        assert!(SeatingLayout::new(u32::MAX, 1, ('F', 'B'), ('L', 'R')).is_err());
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        for layout in [SeatingLayout::standard(), SeatingLayout::wide_body(), SeatingLayout::new(3, 1, ('a', 'b'), ('c', 'd')).unwrap()].iter() {
+            for seat_id in 0..layout.seat_count() {
+                let token = layout.encode_seat_id(seat_id).unwrap();
+                assert_eq!(token.len(), layout.token_length());
+                assert_eq!(layout.decode_seat_id(&token), Ok(seat_id));
+            }
+        }
+
+        assert_eq!(SeatingLayout::standard().encode((44, 5)), Ok("FBFBBFFRLR".to_string()));
+        assert_eq!(SeatingLayout::standard().encode((128, 0)), Err(BoardingPassError::SeatOutOfRange { row: 128, col: 0 }));
+        assert_eq!(SeatingLayout::standard().encode((0, -1)), Err(BoardingPassError::SeatOutOfRange { row: 0, col: -1 }));
+        assert!(SeatingLayout::standard().encode_seat_id(1024).is_err());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let layout = SeatingLayout::standard();
+
+        assert_eq!(layout.decode("FBFBBFFRL"), Err(BoardingPassError::WrongLength { expected: 10, found: 9 }));
+        assert_eq!(layout.decode("FBFBBFFRLRR"), Err(BoardingPassError::WrongLength { expected: 10, found: 11 }));
+        assert_eq!(layout.decode("FBFBBFRRLR"), Err(BoardingPassError::InvalidLetter { position: 6, found: 'R', expected: ('F', 'B') }));
+        assert_eq!(layout.decode("FBFBBFFRXR").unwrap_err().to_string(), "'X' at position 9 should be L or R");
+    }
+}
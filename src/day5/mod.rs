@@ -3,6 +3,12 @@
 use super::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use cursive::views::{EditView, ListView};
+
+pub mod layout;
+pub mod occupancy;
+use layout::{BoardingPassError, SeatingLayout};
+use occupancy::Occupancy;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -19,6 +25,8 @@ fn populate_menu(s: &mut Cursive) {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
         view.add_item("Alternate Method 🌟🌟🌟", 2);
+        view.add_item("Seat Occupancy 💺", 3);
+        view.add_item("Compare Manifests 🆚", 4);
         view.add_item("Back", 999);
     });
 }
@@ -34,6 +42,12 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         2 => {
             alternate_method(s);
         }
+        3 => {
+            seat_occupancy(s);
+        }
+        4 => {
+            compare_manifests_prompt(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -51,7 +65,13 @@ pub fn first_star(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day5_1.txt").unwrap());
             let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
 
-            let max_seat_id = lines.iter().fold(0, |highest_id, line| highest_id.max(seat_id_from_seat_location(process_boarding_token(line))));
+            let layout = SeatingLayout::standard();
+            let all_seat_ids = match decode_all_seat_ids(&layout, &lines) {
+                Ok(seat_ids) => seat_ids,
+                Err(message) => return Ok(message),
+            };
+
+            let max_seat_id = all_seat_ids.iter().fold(0, |highest_id, seat_id| highest_id.max(*seat_id));
             
             Ok(format!("Highest Seat ID: {}", max_seat_id))
         },
@@ -77,15 +97,15 @@ pub fn second_star(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day5_1.txt").unwrap());
             let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
 
-            let all_seat_ids : Vec<i32> = lines.iter().map(|line| seat_id_from_seat_location(process_boarding_token(line))).collect();
-
-            let min_seat_id = *all_seat_ids.iter().min().unwrap();
-            let max_seat_id = *all_seat_ids.iter().max().unwrap();
-
+            let occupancy = match Occupancy::from_tokens(&SeatingLayout::standard(), &lines) {
+                Ok(occupancy) => occupancy,
+                Err(message) => return Ok(message),
+            };
 
-            let missing_id = (min_seat_id .. max_seat_id).find(|seat_id| !all_seat_ids.contains(&seat_id)).unwrap();
-            
-            Ok(format!("My seat id is {}! ", missing_id))
+            match occupancy.candidate_seats().first() {
+                Some(missing_id) => Ok(format!("My seat id is {}! ", missing_id)),
+                None => Ok("There's no free seat between two passengers.".to_string()),
+            }
         },
         TextView::new,
     )
@@ -108,7 +128,10 @@ pub fn alternate_method(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day5_1.txt").unwrap());
             let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
 
-            let all_seat_ids : Vec<i32> = lines.iter().map(|line| process_boarding_token_alternate(line)).collect();
+            let all_seat_ids : Vec<i32> = match lines.iter().map(|line| process_boarding_token_alternate(line)).collect() {
+                Ok(seat_ids) => seat_ids,
+                Err(err) => return Ok(format!("Bad boarding pass: {}", err)),
+            };
             let min_seat_id = *all_seat_ids.iter().min().unwrap();
             let max_seat_id = *all_seat_ids.iter().max().unwrap();
             let missing_id = (min_seat_id .. max_seat_id).find(|seat_id| !all_seat_ids.contains(&seat_id)).unwrap();
@@ -129,20 +152,99 @@ pub fn alternate_method(s: &mut Cursive) {
     );
 }
 
-pub fn process_boarding_token(token: &String) -> (i32, i32) {
+pub fn seat_occupancy(s: &mut Cursive) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let bufreader = BufReader::new(File::open("inputs/day5_1.txt").unwrap());
+            let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+
+            match Occupancy::from_tokens(&SeatingLayout::standard(), &lines) {
+                Ok(occupancy) => Ok(occupancy.summary()),
+                Err(message) => Ok(message),
+            }
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(15)
+    .with_width(50);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Seat Occupancy 💺")
+            .button("Thanks!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn compare_manifests_prompt(s: &mut Cursive) {
+    let paths = ListView::new()
+        .child("First", EditView::new().content("inputs/day5_1.txt").with_name("first_manifest").fixed_width(30))
+        .child("Second", EditView::new().with_name("second_manifest").fixed_width(30));
+
+    s.add_layer(
+        Dialog::around(paths)
+            .title("Compare Manifests 🆚")
+            .button("Compare", |s| {
+                let first = s.call_on_name("first_manifest", |view: &mut EditView| view.get_content()).unwrap();
+                let second = s.call_on_name("second_manifest", |view: &mut EditView| view.get_content()).unwrap();
+                s.pop_layer();
+                compare_manifests(s, first.trim().to_string(), second.trim().to_string());
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn compare_manifests(s: &mut Cursive, first_path: String, second_path: String) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let layout = SeatingLayout::standard();
+            let load = |path: &str| -> Result<Occupancy, String> {
+                let text = std::fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+                let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+                Occupancy::from_tokens(&layout, &lines)
+            };
+
+            let diff = match load(&first_path).and_then(|first| first.diff(&load(&second_path)?)) {
+                Ok(diff) => diff,
+                Err(message) => return Ok(message),
+            };
+
+            Ok(format!("Only in {}: {:?}\n\nOnly in {}: {:?}", first_path, diff.only_in_first, second_path, diff.only_in_second))
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(15)
+    .with_width(50);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Compare Manifests 🆚")
+            .button("Thanks!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn process_boarding_token(token: &str) -> Result<(i32, i32), BoardingPassError> {
     // Token Format: 
     // A 10 character string.
     // First 7 chars are F or B
     // Last 3 chars are L or R
     // A BSP tree, essentially.
+    SeatingLayout::standard().validate(token)?;
 
     let mut maxrow = 127;
     let mut minrow = 0;
     let mut maxcol = 7;
     let mut mincol = 0;
 
-    for idx in 0..10 {
-        match token.chars().nth(idx).unwrap() {
+    for letter in token.chars() {
+        match letter {
             'F' => {
                 maxrow -= ((maxrow - minrow) / 2) + 1;
             }
@@ -155,33 +257,45 @@ pub fn process_boarding_token(token: &String) -> (i32, i32) {
             'R' => {
                 mincol += ((maxcol - mincol) / 2) + 1;
             }
-            _ => {}
+            _ => unreachable!("validate only lets F, B, L and R through")
         }
     }
 
-    (maxrow, maxcol)
+    Ok((maxrow, maxcol))
 }
 
-pub fn process_boarding_token_alternate(token: &String) -> i32 {
+pub fn process_boarding_token_alternate(token: &str) -> Result<i32, BoardingPassError> {
+    SeatingLayout::standard().validate(token)?;
+
     let mut seatid = 0;
 
-    for idx in 0 .. 10 {
-        match token.chars().rev().nth(idx).unwrap() {
-            'B' => {
-                seatid |= 1 << (idx);
-            }
-            'R' => {
-                seatid |= 1 << (idx);
-            }
-            _ => {}
+    for (idx, letter) in token.chars().rev().enumerate() {
+        if letter == 'B' || letter == 'R' {
+            seatid |= 1 << idx;
         }
     }
 
-    seatid
+    Ok(seatid)
+}
+
+pub fn encode_boarding_token(seat: (i32, i32)) -> Result<String, BoardingPassError> {
+    SeatingLayout::standard().encode(seat)
+}
+
+pub fn encode_seat_id(seat_id: i32) -> Result<String, BoardingPassError> {
+    SeatingLayout::standard().encode_seat_id(seat_id)
 }
 
 pub fn seat_id_from_seat_location(seat: (i32, i32)) -> i32 {
-    (seat.0 * 8) + seat.1
+    SeatingLayout::standard().seat_id(seat)
+}
+
+pub fn decode_all_seat_ids(layout: &SeatingLayout, lines: &[String]) -> Result<Vec<i32>, String> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| layout.decode_seat_id(line).map_err(|err| format!("Boarding pass {} ({}): {}", idx + 1, line, err)))
+        .collect()
 }
 
 #[cfg(test)]
@@ -197,15 +311,43 @@ mod day5tests {
             "BBFFBBFRLL".to_string(),
         ];
 
-        assert_eq!(process_boarding_token(&test_tokens[0]), (44, 5));
-        assert_eq!(process_boarding_token(&test_tokens[1]), (70, 7));
-        assert_eq!(process_boarding_token(&test_tokens[2]), (14, 7));
-        assert_eq!(process_boarding_token(&test_tokens[3]), (102, 4));
+        assert_eq!(process_boarding_token(&test_tokens[0]), Ok((44, 5)));
+        assert_eq!(process_boarding_token(&test_tokens[1]), Ok((70, 7)));
+        assert_eq!(process_boarding_token(&test_tokens[2]), Ok((14, 7)));
+        assert_eq!(process_boarding_token(&test_tokens[3]), Ok((102, 4)));
+
+        assert_eq!(process_boarding_token_alternate(&test_tokens[0]), Ok(357));
+        assert_eq!(process_boarding_token_alternate(&test_tokens[1]), Ok(567));
+        assert_eq!(process_boarding_token_alternate(&test_tokens[2]), Ok(119));
+        assert_eq!(process_boarding_token_alternate(&test_tokens[3]), Ok(820));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let bad_tokens = ["FBFBBFFRL", "FBFBBFFRLRL", "FBFBBFFRXR", "FBFBBFRRLR", ""];
+
+        for token in bad_tokens.iter() {
+            assert!(process_boarding_token(token).is_err());
+            assert!(process_boarding_token_alternate(token).is_err());
+        }
+
+        assert_eq!(process_boarding_token("FBFBBFFRL"), Err(BoardingPassError::WrongLength { expected: 10, found: 9 }));
+        assert_eq!(process_boarding_token_alternate("FBFBBFFRXR"), Err(BoardingPassError::InvalidLetter { position: 8, found: 'X', expected: ('L', 'R') }));
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        for seat_id in 0..1024 {
+            let token = encode_seat_id(seat_id).unwrap();
+            let seat = process_boarding_token(&token).unwrap();
+
+            assert_eq!(seat_id_from_seat_location(seat), seat_id);
+            assert_eq!(process_boarding_token_alternate(&token), Ok(seat_id));
+            assert_eq!(encode_boarding_token(seat), Ok(token));
+        }
 
-        assert_eq!(process_boarding_token_alternate(&test_tokens[0]), 357);
-        assert_eq!(process_boarding_token_alternate(&test_tokens[1]), 567);
-        assert_eq!(process_boarding_token_alternate(&test_tokens[2]), 119);
-        assert_eq!(process_boarding_token_alternate(&test_tokens[3]), 820);
+        assert_eq!(encode_boarding_token((102, 4)), Ok("BBFFBBFRLL".to_string()));
+        assert!(encode_seat_id(-1).is_err());
     }
 
     #[test]
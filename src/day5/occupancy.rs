@@ -0,0 +1,267 @@
+// Day 5: Binary Boarding - Occupancy
+// One bit per seat, so everything here is a single pass over the manifest or the bitset.
+use super::layout::SeatingLayout;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeatBitset {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl SeatBitset {
+    pub fn new(len: usize) -> SeatBitset {
+        SeatBitset { words: vec![0; len.div_ceil(64)], len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns false if the bit was already set.
+    pub fn insert(&mut self, idx: usize) -> bool {
+        let mask = 1 << (idx % 64);
+        let word = &mut self.words[idx / 64];
+        let fresh = *word & mask == 0;
+        *word |= mask;
+        fresh
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        idx < self.len && self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn count_range(&self, start: usize, end: usize) -> usize {
+        (start..end).filter(|idx| self.contains(*idx)).count()
+    }
+
+    fn collect_bits(&self, word_bits: impl Fn(usize, u64) -> u64) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for (word_idx, word) in self.words.iter().enumerate() {
+            let mut bits = word_bits(word_idx, *word);
+            while bits != 0 {
+                let idx = word_idx * 64 + bits.trailing_zeros() as usize;
+                // The last word can have spare bits past the end.
+                if idx >= self.len {
+                    break;
+                }
+                indices.push(idx);
+                bits &= bits - 1;
+            }
+        }
+        indices
+    }
+
+    // Indices that are set in self but not in other.
+    pub fn difference(&self, other: &SeatBitset) -> Vec<usize> {
+        self.collect_bits(|word_idx, word| word & !other.words.get(word_idx).copied().unwrap_or(0))
+    }
+
+    pub fn unset(&self) -> Vec<usize> {
+        self.collect_bits(|_, word| !word)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RowFill {
+    pub row: i32,
+    pub occupied: usize,
+    pub seats: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManifestDiff {
+    pub only_in_first: Vec<i32>,
+    pub only_in_second: Vec<i32>,
+}
+
+pub struct Occupancy {
+    pub layout: SeatingLayout,
+    seats: SeatBitset,
+    // One entry per extra boarding pass for an already-taken seat.
+    duplicates: Vec<i32>,
+    passes: usize,
+}
+
+impl Occupancy {
+    pub fn from_seat_ids(layout: &SeatingLayout, seat_ids: &[i32]) -> Result<Occupancy, String> {
+        let mut seats = SeatBitset::new(layout.seat_count() as usize);
+        let mut duplicates = Vec::new();
+
+        for seat_id in seat_ids {
+            if *seat_id < 0 || *seat_id >= layout.seat_count() {
+                return Err(format!("Seat id {} isn't on a plane with {} seats", seat_id, layout.seat_count()));
+            }
+            if !seats.insert(*seat_id as usize) {
+                duplicates.push(*seat_id);
+            }
+        }
+
+        Ok(Occupancy { layout: *layout, seats, duplicates, passes: seat_ids.len() })
+    }
+
+    pub fn from_tokens(layout: &SeatingLayout, lines: &[String]) -> Result<Occupancy, String> {
+        Occupancy::from_seat_ids(layout, &super::decode_all_seat_ids(layout, lines)?)
+    }
+
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    pub fn occupied(&self) -> usize {
+        self.seats.count()
+    }
+
+    pub fn is_occupied(&self, seat_id: i32) -> bool {
+        seat_id >= 0 && self.seats.contains(seat_id as usize)
+    }
+
+    // Empty seats on the part of the plane that's in use, so not counting the empty rows at
+    // the front and back. An empty plane has nothing in use and so nothing missing.
+    pub fn missing_seats(&self) -> Vec<i32> {
+        let cols = self.layout.cols() as usize;
+        let start = self.empty_front_rows() * cols;
+        let end = (self.layout.rows() as usize).saturating_sub(self.empty_back_rows()) * cols;
+        self.seats.unset().into_iter().filter(|idx| *idx >= start && *idx < end).map(|idx| idx as i32).collect()
+    }
+
+    pub fn duplicates(&self) -> &[i32] {
+        &self.duplicates
+    }
+
+    // Empty seats with someone sat either side, which is where the puzzle puts our seat.
+    pub fn candidate_seats(&self) -> Vec<i32> {
+        self.missing_seats().into_iter().filter(|seat_id| self.is_occupied(seat_id - 1) && self.is_occupied(seat_id + 1)).collect()
+    }
+
+    pub fn row_fill(&self) -> Vec<RowFill> {
+        let cols = self.layout.cols() as usize;
+        (0..self.layout.rows())
+            .map(|row| {
+                let start = row as usize * cols;
+                RowFill { row, occupied: self.seats.count_range(start, start + cols), seats: cols }
+            })
+            .collect()
+    }
+
+    // Both count every row on an empty plane, so the two can overlap.
+    pub fn empty_front_rows(&self) -> usize {
+        self.row_fill().iter().take_while(|row| row.occupied == 0).count()
+    }
+
+    pub fn empty_back_rows(&self) -> usize {
+        self.row_fill().iter().rev().take_while(|row| row.occupied == 0).count()
+    }
+
+    pub fn diff(&self, other: &Occupancy) -> Result<ManifestDiff, String> {
+        if self.layout != other.layout {
+            return Err("Can't compare manifests from different seating layouts".to_string());
+        }
+
+        let as_ids = |indices: Vec<usize>| indices.into_iter().map(|idx| idx as i32).collect();
+        Ok(ManifestDiff { only_in_first: as_ids(self.seats.difference(&other.seats)), only_in_second: as_ids(other.seats.difference(&self.seats)) })
+    }
+
+    pub fn summary(&self) -> String {
+        let fill = self.row_fill();
+        let full_rows = fill.iter().filter(|row| row.occupied == row.seats).count();
+        let partial_rows: Vec<String> = fill
+            .iter()
+            .filter(|row| row.occupied > 0 && row.occupied < row.seats)
+            .map(|row| format!("  Row {}: {}/{}", row.row, row.occupied, row.seats))
+            .collect();
+
+        let mut lines = vec![
+            format!("{} boarding passes for {} seats", self.passes, self.layout.seat_count()),
+            format!("Occupied: {}, missing: {}", self.occupied(), self.missing_seats().len()),
+            format!("Duplicate passes: {:?}", self.duplicates),
+            format!("Empty rows: {} at the front, {} at the back", self.empty_front_rows(), self.empty_back_rows()),
+            format!("Full rows: {}", full_rows),
+            "Partly filled rows:".to_string(),
+        ];
+        lines.extend(partial_rows);
+        lines.push(format!("Candidate seats: {:?}", self.candidate_seats()));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod occupancytests {
+    use super::*;
+
+    fn tiny_layout() -> SeatingLayout {
+        SeatingLayout::new(3, 2, ('F', 'B'), ('L', 'R')).unwrap()
+    }
+
+    #[test]
+    fn bitset_works() {
+        let mut bits = SeatBitset::new(130);
+
+        assert!(bits.insert(0));
+        assert!(bits.insert(129));
+        assert!(!bits.insert(129));
+        assert!(bits.contains(129));
+        assert!(!bits.contains(130));
+        assert_eq!(bits.count(), 2);
+        assert_eq!(bits.unset().len(), 128);
+        assert_eq!(bits.count_range(64, 130), 1);
+    }
+
+    #[test]
+    fn missing_and_duplicate_seats_are_found() {
+        let occupancy = Occupancy::from_seat_ids(&tiny_layout(), &[9, 10, 12, 13, 10, 14, 20, 13, 10]).unwrap();
+
+        assert_eq!(occupancy.passes(), 9);
+        assert_eq!(occupancy.occupied(), 6);
+        assert_eq!(occupancy.duplicates(), &[10, 13, 10]);
+        // Rows 0, 1, 6 and 7 are empty, leaving seats 8 to 23.
+        assert_eq!(occupancy.missing_seats(), vec![8, 11, 15, 16, 17, 18, 19, 21, 22, 23]);
+        assert_eq!(occupancy.candidate_seats(), vec![11]);
+
+        assert!(Occupancy::from_seat_ids(&tiny_layout(), &[32]).is_err());
+    }
+
+    #[test]
+    fn row_fill_works() {
+        let occupancy = Occupancy::from_seat_ids(&tiny_layout(), &[9, 10, 12, 13, 14, 15, 20]).unwrap();
+        let fill = occupancy.row_fill();
+
+        assert_eq!(fill.len(), 8);
+        assert_eq!(fill[2], RowFill { row: 2, occupied: 2, seats: 4 });
+        assert_eq!(fill[3].occupied, 4);
+        assert_eq!(occupancy.empty_front_rows(), 2);
+        assert_eq!(occupancy.empty_back_rows(), 2);
+
+        let empty = Occupancy::from_seat_ids(&tiny_layout(), &[]).unwrap();
+        assert_eq!(empty.empty_front_rows(), 8);
+        assert_eq!(empty.empty_back_rows(), 8);
+        assert!(empty.missing_seats().is_empty());
+    }
+
+    #[test]
+    fn manifests_diff() {
+        let first = Occupancy::from_seat_ids(&tiny_layout(), &[1, 2, 3, 31]).unwrap();
+        let second = Occupancy::from_seat_ids(&tiny_layout(), &[2, 3, 4]).unwrap();
+
+        assert_eq!(first.diff(&second), Ok(ManifestDiff { only_in_first: vec![1, 31], only_in_second: vec![4] }));
+
+        let other_plane = Occupancy::from_seat_ids(&SeatingLayout::standard(), &[1]).unwrap();
+        assert!(first.diff(&other_plane).is_err());
+    }
+
+    #[test]
+    fn puzzle_input_has_one_seat_free() {
+        let lines: Vec<String> = std::fs::read_to_string("inputs/day5_1.txt").unwrap().lines().map(|line| line.to_string()).collect();
+        let occupancy = Occupancy::from_tokens(&SeatingLayout::standard(), &lines).unwrap();
+
+        assert!(occupancy.duplicates().is_empty());
+        assert_eq!(occupancy.candidate_seats().len(), 1);
+    }
+}
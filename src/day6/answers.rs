@@ -0,0 +1,212 @@
+// Day 6: Custom Customs - Answer Sets
+// Questions a to z are one bit each in a u32. Anything else goes in a small set on
+// the side, which stays empty (and unallocated) for the puzzle's own input.
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnswerSet {
+    mask: u32,
+    other: BTreeSet<char>,
+}
+
+fn bit_for(question: char) -> Option<u32> {
+    if question.is_ascii_lowercase() {
+        Some(1 << (question as u32 - 'a' as u32))
+    } else {
+        None
+    }
+}
+
+impl AnswerSet {
+    pub fn new() -> AnswerSet {
+        AnswerSet::default()
+    }
+
+    // One person's line of answers; whitespace is ignored.
+    pub fn from_answers(answers: &str) -> AnswerSet {
+        let mut set = AnswerSet::new();
+        answers.chars().filter(|chr| !chr.is_whitespace()).for_each(|chr| set.insert(chr));
+        set
+    }
+
+    pub fn insert(&mut self, question: char) {
+        match bit_for(question) {
+            Some(bit) => self.mask |= bit,
+            None => {
+                self.other.insert(question);
+            }
+        }
+    }
+
+    pub fn contains(&self, question: char) -> bool {
+        match bit_for(question) {
+            Some(bit) => self.mask & bit != 0,
+            None => self.other.contains(&question),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mask.count_ones() as usize + self.other.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask == 0 && self.other.is_empty()
+    }
+
+    pub fn union(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet { mask: self.mask | other.mask, other: self.other.union(&other.other).copied().collect() }
+    }
+
+    pub fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet { mask: self.mask & other.mask, other: self.other.intersection(&other.other).copied().collect() }
+    }
+
+    pub fn symmetric_difference(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet { mask: self.mask ^ other.mask, other: self.other.symmetric_difference(&other.other).copied().collect() }
+    }
+
+    pub fn questions(&self) -> Vec<char> {
+        let mut questions: Vec<char> = ('a'..='z').filter(|chr| self.contains(*chr)).chain(self.other.iter().copied()).collect();
+        questions.sort_unstable();
+        questions
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupAnswers {
+    pub people: u32,
+    // Questions anyone in the group said yes to, and questions everyone did.
+    pub any: AnswerSet,
+    pub every: AnswerSet,
+}
+
+impl GroupAnswers {
+    pub fn add_person(&mut self, answers: &AnswerSet) {
+        self.every = if self.people == 0 { answers.clone() } else { self.every.intersection(answers) };
+        self.any = self.any.union(answers);
+        self.people += 1;
+    }
+}
+
+// Splits lines into groups at blank lines without holding more than one group at a time.
+pub struct GroupReader<I> {
+    lines: I,
+}
+
+impl<I, S> Iterator for GroupReader<I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    type Item = GroupAnswers;
+
+    fn next(&mut self) -> Option<GroupAnswers> {
+        let mut group = GroupAnswers::default();
+
+        for line in &mut self.lines {
+            let line = line.as_ref().trim();
+            if line.is_empty() {
+                if group.people > 0 {
+                    return Some(group);
+                }
+            } else {
+                group.add_person(&AnswerSet::from_answers(line));
+            }
+        }
+
+        if group.people > 0 {
+            Some(group)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn read_groups<I, S>(lines: I) -> GroupReader<I::IntoIter>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    GroupReader { lines: lines.into_iter() }
+}
+
+pub fn collect_groups_as_sets(lines: &[String]) -> Vec<GroupAnswers> {
+    read_groups(lines).collect()
+}
+
+// (questions anyone answered yes, questions everyone answered yes), summed over all groups.
+pub fn total_yes_answers<I: IntoIterator<Item = GroupAnswers>>(groups: I) -> (u64, u64) {
+    groups.into_iter().fold((0, 0), |(any, every), group| (any + group.any.len() as u64, every + group.every.len() as u64))
+}
+
+#[cfg(test)]
+mod answerstests {
+    use super::*;
+    use crate::day6::{calculate_total_questions_answered_any_yes, calculate_total_questions_answered_every_yes, collect_groups_as_dicts};
+
+    fn get_test_lines() -> Vec<String> {
+        ["abc", "", "a", "b", "c", "", "ab", "ac", "", "a", "a", "a", "a", "", "b"].iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn set_operations_work() {
+        let first = AnswerSet::from_answers("abcz");
+        let second = AnswerSet::from_answers("b z y");
+
+        assert_eq!(first.len(), 4);
+        assert_eq!(first.union(&second).questions(), vec!['a', 'b', 'c', 'y', 'z']);
+        assert_eq!(first.intersection(&second).questions(), vec!['b', 'z']);
+        assert_eq!(first.symmetric_difference(&second).questions(), vec!['a', 'c', 'y']);
+        assert!(first.intersection(&AnswerSet::from_answers("q")).is_empty());
+    }
+
+    #[test]
+    fn non_ascii_questions_fall_back() {
+        let first = AnswerSet::from_answers("aé🦀Z");
+        let second = AnswerSet::from_answers("éZb");
+
+        assert_eq!(first.len(), 4);
+        assert!(first.contains('🦀'));
+        assert!(!first.contains('b'));
+        assert_eq!(first.intersection(&second).questions(), vec!['Z', 'é']);
+        assert_eq!(first.symmetric_difference(&second).questions(), vec!['a', 'b', '🦀']);
+        assert_eq!(first.union(&second).len(), 5);
+    }
+
+    #[test]
+    fn groups_match_the_dict_version() {
+        let lines = get_test_lines();
+        let groups = collect_groups_as_sets(&lines);
+        let dicts = collect_groups_as_dicts(&lines);
+
+        assert_eq!(groups.len(), 5);
+        assert_eq!(groups[1].people, 3);
+        assert_eq!(groups[2].every.questions(), vec!['a']);
+        assert_eq!(total_yes_answers(groups), (11, 6));
+        assert_eq!(calculate_total_questions_answered_any_yes(&dicts), 11);
+        assert_eq!(calculate_total_questions_answered_every_yes(&dicts), 6);
+
+        let lines = std::fs::read_to_string("inputs/day6_1.txt").unwrap();
+        let dicts = collect_groups_as_dicts(&lines.lines().map(|line| line.to_string()).collect());
+        let totals = total_yes_answers(read_groups(lines.lines()));
+        assert_eq!(totals.0, calculate_total_questions_answered_any_yes(&dicts) as u64);
+        assert_eq!(totals.1, calculate_total_questions_answered_every_yes(&dicts) as u64);
+    }
+
+    #[test]
+    fn extra_blank_lines_dont_make_empty_groups() {
+        let groups: Vec<GroupAnswers> = read_groups(vec!["", "ab", "", "", "b", ""]).collect();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(total_yes_answers(groups), (3, 3));
+    }
+
+    #[test]
+    fn large_surveys_stream() {
+        // A million respondents in groups of four, generated lazily.
+        let lines = (0..1_250_000u32).map(|idx| if idx % 5 == 4 { String::new() } else { format!("ab{}", (b'c' + (idx % 5) as u8) as char) });
+        let totals = total_yes_answers(read_groups(lines));
+
+        assert_eq!(totals, (250_000 * 6, 250_000 * 2));
+    }
+}
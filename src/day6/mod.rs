@@ -4,6 +4,10 @@ use super::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::collections::HashMap;
+use cursive::views::{EditView, LinearLayout};
+
+pub mod answers;
+pub mod queries;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -19,6 +23,7 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Survey Queries 📊", 2);
         view.add_item("Back", 999);
     });
 }
@@ -31,6 +36,9 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            survey_query_prompt(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -46,13 +54,12 @@ pub fn first_star(s: &mut Cursive) {
         s,
         move || {
             let bufreader = BufReader::new(File::open("inputs/day6_1.txt").unwrap());
-            let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
-
-            let groups = collect_groups_as_dicts(&lines);
-            let total_yes_questions = calculate_total_questions_answered_any_yes(&groups);
+            let groups : Vec<answers::GroupAnswers> = answers::read_groups(bufreader.lines().map(|line| line.unwrap())).collect();
+            let group_count = groups.len();
+            let total_yes_questions = answers::total_yes_answers(groups).0;
 
             
-            Ok(format!("Total number of groups: {}\nTotal questions answered yes: {}", groups.len(), total_yes_questions))
+            Ok(format!("Total number of groups: {}\nTotal questions answered yes: {}", group_count, total_yes_questions))
         },
         TextView::new,
     )
@@ -74,13 +81,12 @@ pub fn second_star(s: &mut Cursive) {
         s,
         move || {
             let bufreader = BufReader::new(File::open("inputs/day6_1.txt").unwrap());
-            let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
-
-            let groups = collect_groups_as_dicts(&lines);
-            let total_yes_questions = calculate_total_questions_answered_every_yes(&groups);
+            let groups : Vec<answers::GroupAnswers> = answers::read_groups(bufreader.lines().map(|line| line.unwrap())).collect();
+            let group_count = groups.len();
+            let total_yes_questions = answers::total_yes_answers(groups).1;
 
             
-            Ok(format!("Total number of groups: {}\nTotal questions where everyone answered yes: {}", groups.len(), total_yes_questions))
+            Ok(format!("Total number of groups: {}\nTotal questions where everyone answered yes: {}", group_count, total_yes_questions))
         },
         TextView::new,
     )
@@ -96,6 +102,54 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
+pub fn survey_query_prompt(s: &mut Cursive) {
+    let prompt = LinearLayout::vertical()
+        .child(TextView::new("at least k, at most k, exactly k or everyone"))
+        .child(EditView::new().content("at least 2").with_name("survey_query").fixed_width(30));
+
+    s.add_layer(
+        Dialog::around(prompt)
+            .title("Survey Queries 📊")
+            .button("Ask", |s| {
+                let query = s.call_on_name("survey_query", |view: &mut EditView| view.get_content()).unwrap();
+                match query.parse::<queries::Threshold>() {
+                    Ok(threshold) => {
+                        s.pop_layer();
+                        survey_query(s, threshold);
+                    }
+                    Err(message) => s.add_layer(Dialog::info(message)),
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn survey_query(s: &mut Cursive, threshold: queries::Threshold) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let bufreader = BufReader::new(File::open("inputs/day6_1.txt").unwrap());
+            let lines : Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+
+            let groups = collect_groups_as_dicts(&lines);
+            Ok(queries::describe_query(&groups, threshold))
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(45);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Survey Queries 📊")
+            .button("Interesting!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
 pub fn collect_groups_as_dicts(lines: &Vec<String>) -> Vec<(i32, HashMap<char, i32>)> {
     let mut hashmap_vec = Vec::new();
     let mut current_hashmap: HashMap<char, i32> = HashMap::new();
@@ -0,0 +1,199 @@
+// Day 6: Custom Customs - Queries
+// Questions over the groups from collect_groups_as_dicts. Only questions somebody in a
+// group answered yes to are in its map, so a question nobody answered never matches.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+pub type Group = (i32, HashMap<char, i32>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    AtLeast(i32),
+    AtMost(i32),
+    Exactly(i32),
+    Everyone,
+}
+
+impl Threshold {
+    pub fn matches(&self, yes_count: i32, group_size: i32) -> bool {
+        if yes_count == 0 {
+            return false;
+        }
+
+        match self {
+            Threshold::AtLeast(k) => yes_count >= *k,
+            Threshold::AtMost(k) => yes_count <= *k,
+            Threshold::Exactly(k) => yes_count == *k,
+            Threshold::Everyone => yes_count == group_size,
+        }
+    }
+}
+
+// "at least 2", "at most 1", "exactly 3" or "everyone".
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Threshold, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let count = |word: &str| word.parse::<i32>().map_err(|_| format!("{} isn't a number of people", word));
+
+        match words.as_slice() {
+            ["everyone"] => Ok(Threshold::Everyone),
+            ["at", "least", k] => Ok(Threshold::AtLeast(count(k)?)),
+            ["at", "most", k] => Ok(Threshold::AtMost(count(k)?)),
+            ["exactly", k] => Ok(Threshold::Exactly(count(k)?)),
+            _ => Err(format!("Don't know how to ask \"{}\"", text.trim())),
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Threshold::AtLeast(k) => write!(f, "at least {}", k),
+            Threshold::AtMost(k) => write!(f, "at most {}", k),
+            Threshold::Exactly(k) => write!(f, "exactly {}", k),
+            Threshold::Everyone => write!(f, "everyone"),
+        }
+    }
+}
+
+pub fn questions_matching(group: &Group, threshold: Threshold) -> Vec<char> {
+    let mut questions: Vec<char> = group.1.iter().filter(|(_, yes_count)| threshold.matches(**yes_count, group.0)).map(|(question, _)| *question).collect();
+    questions.sort_unstable();
+    questions
+}
+
+pub fn count_matching(groups: &[Group], threshold: Threshold) -> i32 {
+    groups.iter().map(|group| questions_matching(group, threshold).len() as i32).sum()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuestionFrequency {
+    pub question: char,
+    // People across every group who answered yes.
+    pub yes_answers: i32,
+    // Groups where at least one person answered yes.
+    pub groups: i32,
+}
+
+pub fn question_histogram(groups: &[Group]) -> Vec<QuestionFrequency> {
+    let mut histogram: BTreeMap<char, QuestionFrequency> = BTreeMap::new();
+
+    for (_, answers) in groups {
+        for (question, yes_count) in answers {
+            let entry = histogram.entry(*question).or_insert(QuestionFrequency { question: *question, yes_answers: 0, groups: 0 });
+            entry.yes_answers += yes_count;
+            entry.groups += 1;
+        }
+    }
+
+    histogram.into_values().collect()
+}
+
+pub fn render_histogram(histogram: &[QuestionFrequency], width: usize) -> String {
+    let most = histogram.iter().map(|frequency| frequency.yes_answers).max().unwrap_or(0).max(1);
+
+    histogram
+        .iter()
+        .map(|frequency| {
+            let bar = "#".repeat(frequency.yes_answers as usize * width / most as usize);
+            format!("{} {:>5} {}", frequency.question, frequency.yes_answers, bar)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroupExtremes {
+    // (group index, shared answers); the first group wins ties.
+    pub most_shared: (usize, usize),
+    pub fewest_shared: (usize, usize),
+}
+
+pub fn shared_answers(group: &Group) -> usize {
+    questions_matching(group, Threshold::Everyone).len()
+}
+
+pub fn group_extremes(groups: &[Group]) -> Option<GroupExtremes> {
+    let shared: Vec<(usize, usize)> = groups.iter().map(shared_answers).enumerate().collect();
+
+    let most_shared = *shared.iter().rev().max_by_key(|(_, count)| *count)?;
+    let fewest_shared = *shared.iter().min_by_key(|(_, count)| *count)?;
+
+    Some(GroupExtremes { most_shared, fewest_shared })
+}
+
+pub fn describe_query(groups: &[Group], threshold: Threshold) -> String {
+    let mut lines = vec![format!("Questions where {} said yes: {}", threshold, count_matching(groups, threshold))];
+
+    if let Some(extremes) = group_extremes(groups) {
+        lines.push(format!("Most shared answers: group {} ({})", extremes.most_shared.0 + 1, extremes.most_shared.1));
+        lines.push(format!("Fewest shared answers: group {} ({})", extremes.fewest_shared.0 + 1, extremes.fewest_shared.1));
+    }
+
+    lines.push(String::new());
+    lines.push("Yes answers per question:".to_string());
+    lines.push(render_histogram(&question_histogram(groups), 20));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod queriestests {
+    use super::*;
+    use crate::day6::collect_groups_as_dicts;
+
+    fn get_test_groups() -> Vec<Group> {
+        let lines: Vec<String> = ["abc", "", "a", "b", "c", "", "ab", "ac", "", "a", "a", "a", "a", "", "b"].iter().map(|line| line.to_string()).collect();
+        collect_groups_as_dicts(&lines)
+    }
+
+    #[test]
+    fn thresholds_parse() {
+        assert_eq!("at least 2".parse(), Ok(Threshold::AtLeast(2)));
+        assert_eq!(" at  most 1 ".parse(), Ok(Threshold::AtMost(1)));
+        assert_eq!("exactly 3".parse(), Ok(Threshold::Exactly(3)));
+        assert_eq!("everyone".parse(), Ok(Threshold::Everyone));
+        assert!("at least two".parse::<Threshold>().is_err());
+        assert!("most".parse::<Threshold>().is_err());
+        assert_eq!(Threshold::AtMost(4).to_string(), "at most 4");
+    }
+
+    #[test]
+    fn threshold_queries_work() {
+        let groups = get_test_groups();
+
+        // The two stars are special cases.
+        assert_eq!(count_matching(&groups, Threshold::AtLeast(1)), 11);
+        assert_eq!(count_matching(&groups, Threshold::Everyone), 6);
+
+        assert_eq!(count_matching(&groups, Threshold::AtLeast(2)), 2);
+        assert_eq!(count_matching(&groups, Threshold::AtMost(1)), 9);
+        assert_eq!(count_matching(&groups, Threshold::Exactly(4)), 1);
+        assert_eq!(questions_matching(&groups[2], Threshold::Exactly(1)), vec!['b', 'c']);
+    }
+
+    #[test]
+    fn histogram_works() {
+        let histogram = question_histogram(&get_test_groups());
+
+        assert_eq!(
+            histogram,
+            vec![
+                QuestionFrequency { question: 'a', yes_answers: 8, groups: 4 },
+                QuestionFrequency { question: 'b', yes_answers: 4, groups: 4 },
+                QuestionFrequency { question: 'c', yes_answers: 3, groups: 3 },
+            ]
+        );
+        assert_eq!(render_histogram(&histogram, 8).lines().next(), Some("a     8 ########"));
+    }
+
+    #[test]
+    fn group_extremes_work() {
+        let groups = get_test_groups();
+
+        assert_eq!(group_extremes(&groups), Some(GroupExtremes { most_shared: (0, 3), fewest_shared: (1, 0) }));
+        assert_eq!(group_extremes(&[]), None);
+    }
+}
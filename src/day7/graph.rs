@@ -0,0 +1,401 @@
+// Day 7: Handy Haversacks - Bag Graph
+// Bags are interned to ids once, with edges both ways. Ancestor and descendant sets are
+// worked out the first time they're asked for and kept. Cyclic rules are refused up
+// front, so every walk here is guaranteed to bottom out.
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+pub type BagId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BagGraphError {
+    UnknownBag(String),
+    // The bags on the cycle, starting and ending with the same one.
+    Cycle(Vec<String>),
+    DuplicateRule(String),
+    Overflow(String),
+}
+
+impl fmt::Display for BagGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BagGraphError::UnknownBag(bag) => write!(f, "there's no rule mentioning {} bags", bag),
+            BagGraphError::Cycle(bags) => write!(f, "bags contain themselves: {}", bags.join(" -> ")),
+            BagGraphError::DuplicateRule(bag) => write!(f, "{} bags have more than one rule", bag),
+            BagGraphError::Overflow(bag) => write!(f, "too many bags inside {} to count", bag),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, BagId>,
+    // Bags with a rule of their own, in the order the rules were given.
+    rule_order: Vec<BagId>,
+    contents: Vec<Vec<(BagId, u64)>>,
+    containers: Vec<Vec<(BagId, u64)>>,
+    ancestors: Vec<OnceCell<Vec<BagId>>>,
+    descendants: Vec<OnceCell<Vec<BagId>>>,
+    totals: Vec<OnceCell<Option<u64>>>,
+}
+
+impl BagGraph {
+    pub fn build<I>(rules: I) -> Result<BagGraph, BagGraphError>
+    where
+        I: IntoIterator<Item = (String, Vec<(String, u64)>)>,
+    {
+        let mut graph = BagGraph {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            rule_order: Vec::new(),
+            contents: Vec::new(),
+            containers: Vec::new(),
+            ancestors: Vec::new(),
+            descendants: Vec::new(),
+            totals: Vec::new(),
+        };
+
+        let mut ruled: HashSet<BagId> = HashSet::new();
+        for (container, contents) in rules {
+            let container_id = graph.intern(&container);
+            if !ruled.insert(container_id) {
+                return Err(BagGraphError::DuplicateRule(container));
+            }
+            graph.rule_order.push(container_id);
+
+            for (bag, count) in contents {
+                let bag_id = graph.intern(&bag);
+                graph.contents[container_id].push((bag_id, count));
+                graph.containers[bag_id].push((container_id, count));
+            }
+        }
+
+        graph.ancestors = (0..graph.len()).map(|_| OnceCell::new()).collect();
+        graph.descendants = (0..graph.len()).map(|_| OnceCell::new()).collect();
+        graph.totals = (0..graph.len()).map(|_| OnceCell::new()).collect();
+
+        graph.check_acyclic()?;
+        Ok(graph)
+    }
+
+    // Rules in the shape find_all_rules produces. Map order isn't stable, so rules are taken alphabetically.
+    pub fn from_rules(rules: &HashMap<String, HashMap<String, i32>>) -> Result<BagGraph, BagGraphError> {
+        let mut sorted_rules: Vec<(String, Vec<(String, u64)>)> = rules
+            .iter()
+            .map(|(bag, contents)| {
+                let mut contents: Vec<(String, u64)> = contents.iter().map(|(inner, count)| (inner.to_string(), (*count).max(0) as u64)).collect();
+                contents.sort();
+                (bag.to_string(), contents)
+            })
+            .collect();
+        sorted_rules.sort();
+
+        BagGraph::build(sorted_rules)
+    }
+
+    fn intern(&mut self, bag: &str) -> BagId {
+        if let Some(id) = self.ids.get(bag) {
+            return *id;
+        }
+
+        let id = self.names.len();
+        self.names.push(bag.to_string());
+        self.ids.insert(bag.to_string(), id);
+        self.contents.push(Vec::new());
+        self.containers.push(Vec::new());
+        id
+    }
+
+    fn check_acyclic(&self) -> Result<(), BagGraphError> {
+        // 0 = not seen, 1 = on the current path, 2 = done.
+        let mut state = vec![0u8; self.len()];
+
+        for start in 0..self.len() {
+            if state[start] != 0 {
+                continue;
+            }
+
+            // Walk depth first with an explicit stack of (bag, next child to look at).
+            let mut stack: Vec<(BagId, usize)> = vec![(start, 0)];
+            state[start] = 1;
+
+            while let Some((bag, child_idx)) = stack.last_mut() {
+                let bag = *bag;
+                match self.contents[bag].get(*child_idx) {
+                    Some((child, _)) => {
+                        *child_idx += 1;
+                        match state[*child] {
+                            0 => {
+                                state[*child] = 1;
+                                stack.push((*child, 0));
+                            }
+                            1 => {
+                                let cycle_start = stack.iter().position(|(on_path, _)| on_path == child).unwrap();
+                                let mut cycle: Vec<String> = stack[cycle_start..].iter().map(|(on_path, _)| self.names[*on_path].to_string()).collect();
+                                cycle.push(self.names[*child].to_string());
+                                return Err(BagGraphError::Cycle(cycle));
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => {
+                        state[bag] = 2;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, bag: &str) -> Result<BagId, BagGraphError> {
+        self.ids.get(bag).copied().ok_or_else(|| BagGraphError::UnknownBag(bag.to_string()))
+    }
+
+    pub fn name(&self, id: BagId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn rule_order(&self) -> &[BagId] {
+        &self.rule_order
+    }
+
+    pub fn has_rule(&self, id: BagId) -> bool {
+        self.rule_order.contains(&id)
+    }
+
+    pub fn contents(&self, id: BagId) -> &[(BagId, u64)] {
+        &self.contents[id]
+    }
+
+    pub fn containers(&self, id: BagId) -> &[(BagId, u64)] {
+        &self.containers[id]
+    }
+
+    // Each bag's set is its neighbours' sets merged together, so it only ever costs as much
+    // as the sets it's built from rather than a pass over every bag. Filled in depth first
+    // with an explicit stack, as in check_acyclic, so deep nesting can't run out of stack:
+    // a bag's set is made once all of its neighbours' are.
+    fn closure<'a>(&'a self, id: BagId, memo: &'a [OnceCell<Vec<BagId>>], edges: &[Vec<(BagId, u64)>]) -> &'a [BagId] {
+        let mut stack: Vec<(BagId, usize)> = vec![(id, 0)];
+
+        while let Some((bag, next_idx)) = stack.last_mut() {
+            let bag = *bag;
+            if memo[bag].get().is_some() {
+                stack.pop();
+                continue;
+            }
+
+            match edges[bag].get(*next_idx) {
+                Some((next, _)) => {
+                    *next_idx += 1;
+                    if memo[*next].get().is_none() {
+                        stack.push((*next, 0));
+                    }
+                }
+                None => {
+                    let mut reached: Vec<BagId> = Vec::new();
+                    for (next, _) in &edges[bag] {
+                        reached = merge_sorted(&reached, std::slice::from_ref(next));
+                        reached = merge_sorted(&reached, memo[*next].get().unwrap());
+                    }
+                    let _ = memo[bag].set(reached);
+                    stack.pop();
+                }
+            }
+        }
+
+        memo[id].get().unwrap()
+    }
+
+    // Every bag that could end up holding this one, sorted by id.
+    pub fn ancestors(&self, id: BagId) -> &[BagId] {
+        self.closure(id, &self.ancestors, &self.containers)
+    }
+
+    // Every bag that could end up inside this one, sorted by id.
+    pub fn descendants(&self, id: BagId) -> &[BagId] {
+        self.closure(id, &self.descendants, &self.contents)
+    }
+
+    // Worked out the same way as closure, bottom up from an explicit stack.
+    fn checked_total(&self, id: BagId) -> Option<u64> {
+        let mut stack: Vec<(BagId, usize)> = vec![(id, 0)];
+
+        while let Some((bag, inner_idx)) = stack.last_mut() {
+            let bag = *bag;
+            if self.totals[bag].get().is_some() {
+                stack.pop();
+                continue;
+            }
+
+            match self.contents[bag].get(*inner_idx) {
+                Some((inner, _)) => {
+                    *inner_idx += 1;
+                    if self.totals[*inner].get().is_none() {
+                        stack.push((*inner, 0));
+                    }
+                }
+                None => {
+                    let total = self.contents[bag].iter().try_fold(0u64, |total, (inner, count)| {
+                        let inner_total = (*self.totals[*inner].get().unwrap())?;
+                        total.checked_add(count.checked_mul(inner_total.checked_add(1)?)?)
+                    });
+                    let _ = self.totals[bag].set(total);
+                    stack.pop();
+                }
+            }
+        }
+
+        *self.totals[id].get().unwrap()
+    }
+
+    // How many bags in total end up inside this one.
+    pub fn total_inside(&self, id: BagId) -> Result<u64, BagGraphError> {
+        self.checked_total(id).ok_or_else(|| BagGraphError::Overflow(self.names[id].to_string()))
+    }
+
+    pub fn count_containers_of(&self, bag: &str) -> Result<usize, BagGraphError> {
+        Ok(self.ancestors(self.id(bag)?).len())
+    }
+
+    pub fn total_inside_of(&self, bag: &str) -> Result<u64, BagGraphError> {
+        self.total_inside(self.id(bag)?)
+    }
+}
+
+// Both sorted with no repeats, and so is the result.
+fn merge_sorted(left: &[BagId], right: &[BagId]) -> Vec<BagId> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left_idx, mut right_idx) = (0, 0);
+
+    while left_idx < left.len() && right_idx < right.len() {
+        let (left_bag, right_bag) = (left[left_idx], right[right_idx]);
+        merged.push(left_bag.min(right_bag));
+        if left_bag <= right_bag {
+            left_idx += 1;
+        }
+        if right_bag <= left_bag {
+            right_idx += 1;
+        }
+    }
+    merged.extend_from_slice(&left[left_idx..]);
+    merged.extend_from_slice(&right[right_idx..]);
+    merged
+}
+
+#[cfg(test)]
+mod graphtests {
+    use super::*;
+    use crate::day7::find_all_rules;
+
+    fn get_test_rules() -> Vec<String> {
+        [
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+            "dark orange bags contain 3 bright white bags, 4 muted yellow bags.",
+            "bright white bags contain 1 shiny gold bag.",
+            "muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.",
+            "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.",
+            "dark olive bags contain 3 faded blue bags, 4 dotted black bags.",
+            "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.",
+            "faded blue bags contain no other bags.",
+            "dotted black bags contain no other bags.",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+    }
+
+    fn rule(bag: &str, contents: &[(&str, u64)]) -> (String, Vec<(String, u64)>) {
+        (bag.to_string(), contents.iter().map(|(inner, count)| (inner.to_string(), *count)).collect())
+    }
+
+    #[test]
+    fn graph_answers_match_puzzle() {
+        let graph = BagGraph::from_rules(&find_all_rules(&get_test_rules()).unwrap()).unwrap();
+
+        assert_eq!(graph.len(), 9);
+        assert_eq!(graph.count_containers_of("shiny gold"), Ok(4));
+        assert_eq!(graph.total_inside_of("shiny gold"), Ok(32));
+        assert_eq!(graph.descendants(graph.id("light red").unwrap()).len(), 7);
+        assert_eq!(graph.containers(graph.id("shiny gold").unwrap()).len(), 2);
+        assert_eq!(graph.id("plaid purple"), Err(BagGraphError::UnknownBag("plaid purple".to_string())));
+    }
+
+    #[test]
+    fn graph_matches_old_recursion_on_input() {
+        let lines: Vec<String> = std::fs::read_to_string("inputs/day7.txt").unwrap().lines().map(|line| line.to_string()).collect();
+        let rules = find_all_rules(&lines).unwrap();
+        let graph = BagGraph::from_rules(&rules).unwrap();
+
+        assert_eq!(graph.total_inside_of("shiny gold"), Ok(crate::day7::count_total_bags_inside_bag_recursive("shiny gold".to_string(), &rules) as u64));
+    }
+
+    #[test]
+    fn cycles_are_refused() {
+        let rules = vec![rule("a", &[("b", 1)]), rule("b", &[("c", 2)]), rule("c", &[("a", 1)]), rule("d", &[])];
+
+        match BagGraph::build(rules) {
+            Err(BagGraphError::Cycle(bags)) => assert_eq!(bags, vec!["a", "b", "c", "a"]),
+            _ => panic!("cycle wasn't found"),
+        }
+
+        assert!(BagGraph::build(vec![rule("a", &[("a", 1)])]).is_err());
+        assert_eq!(BagGraph::build(vec![rule("a", &[]), rule("a", &[])]).err(), Some(BagGraphError::DuplicateRule("a".to_string())));
+    }
+
+    #[test]
+    fn totals_are_checked() {
+        // Every level multiplies by a million, so the bottom of the chain overflows a u64.
+        let chain: Vec<(String, Vec<(String, u64)>)> = (0..4).map(|level| rule(&format!("level {}", level), &[(&format!("level {}", level + 1), 1_000_000)])).collect();
+        let graph = BagGraph::build(chain).unwrap();
+
+        assert_eq!(graph.total_inside_of("level 2"), Ok(1_000_000 + 1_000_000 * 1_000_000));
+        assert_eq!(graph.total_inside_of("level 0"), Err(BagGraphError::Overflow("level 0".to_string())));
+    }
+
+    #[test]
+    fn deep_nesting_is_fine() {
+        // Far deeper than the call stack would go, one bag inside the next.
+        let depth = 200_000;
+        let chain: Vec<(String, Vec<(String, u64)>)> = (0..depth).map(|level| rule(&format!("level {}", level), &[(&format!("level {}", level + 1), 1)])).collect();
+        let graph = BagGraph::build(chain).unwrap();
+
+        assert_eq!(graph.total_inside_of("level 0"), Ok(depth as u64));
+        assert_eq!(graph.count_containers_of("level 10"), Ok(10));
+
+        // Each level's set holds everything below it, so the sets are kept to a shallower chain.
+        let depth = 20_000;
+        let chain: Vec<(String, Vec<(String, u64)>)> = (0..depth).map(|level| rule(&format!("level {}", level), &[(&format!("level {}", level + 1), 1)])).collect();
+        let graph = BagGraph::build(chain).unwrap();
+
+        assert_eq!(graph.descendants(graph.id("level 0").unwrap()).len(), depth);
+        assert_eq!(graph.count_containers_of(&format!("level {}", depth)), Ok(depth));
+    }
+
+    #[test]
+    fn shared_sub_bags_are_counted_once_in_sets() {
+        let graph = BagGraph::build(vec![rule("top", &[("left", 1), ("right", 1)]), rule("left", &[("bottom", 2)]), rule("right", &[("bottom", 3)])]).unwrap();
+
+        assert_eq!(graph.descendants(graph.id("top").unwrap()).len(), 3);
+        assert_eq!(graph.ancestors(graph.id("bottom").unwrap()).len(), 3);
+        assert_eq!(graph.descendants(graph.id("top").unwrap()), &[1, 2, 3]);
+        assert_eq!(merge_sorted(&[0, 2, 5], &[1, 2, 6]), vec![0, 1, 2, 5, 6]);
+        assert_eq!(graph.total_inside_of("top"), Ok(1 + 2 + 1 + 3));
+    }
+}
@@ -1,11 +1,15 @@
 // Day 7: Handy Haversacks
 // https://adventofcode.com/2020/day/7
 use super::*;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use regex::*;
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use cursive::views::{EditView, LinearLayout};
+
+pub mod graph;
+pub mod query;
+pub mod rules;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -21,6 +25,8 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Tidy Rules 📝", 2);
+        view.add_item("Bag Queries ❓", 3);
         view.add_item("Back", 999);
     });
 }
@@ -33,6 +39,12 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            tidy_rules(s);
+        }
+        3 => {
+            bag_query_prompt(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -46,12 +58,13 @@ pub fn first_star(s: &mut Cursive) {
         s,
         move || {
             // Load input file and parse it into a vec of ints
-            let bufreader = BufReader::new(File::open("inputs/day7.txt").unwrap());
-            let lines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
-            let bags = find_all_kinds_of_bag(&lines);
-            let rules = find_all_rules(&lines);
-            
-            Ok(format!("Wow! There are {} bags that eventually contain the shiny gold bag.", count_bags_that_contain_bag("shiny gold".to_string(), &bags, &rules)))
+            let text = std::fs::read_to_string("inputs/day7.txt").unwrap();
+            let containers = rules::parse_graph(&text).and_then(|graph| graph.count_containers_of("shiny gold").map_err(|err| err.to_string()));
+
+            match containers {
+                Ok(count) => Ok(format!("Wow! There are {} bags that eventually contain the shiny gold bag.", count)),
+                Err(err) => Ok(format!("Those rules don't work: {}", err)),
+            }
         },
         TextView::new,
     )
@@ -73,11 +86,13 @@ pub fn second_star(s: &mut Cursive) {
         s,
         move || {
             // Load input file and parse it into a vec of ints
-            let bufreader = BufReader::new(File::open("inputs/day7.txt").unwrap());
-            let lines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
-            let rules = find_all_rules(&lines);
+            let text = std::fs::read_to_string("inputs/day7.txt").unwrap();
+            let total = rules::parse_graph(&text).and_then(|graph| graph.total_inside_of("shiny gold").map_err(|err| err.to_string()));
 
-            Ok(format!("Aw jeez! The shiny gold bag contains {} other bags!", count_total_bags_inside_bag_recursive("shiny gold".to_string(), &rules)))
+            match total {
+                Ok(total) => Ok(format!("Aw jeez! The shiny gold bag contains {} other bags!", total)),
+                Err(err) => Ok(format!("Those rules don't work: {}", err)),
+            }
         },
         TextView::new,
     )
@@ -93,6 +108,85 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
+// Parses the rules and writes them back out, so hand-edited rule sets come out consistent.
+pub fn tidy_rules(s: &mut Cursive) {
+    let text = std::fs::read_to_string("inputs/day7.txt").unwrap();
+
+    let message = match rules::parse_graph(&text) {
+        Ok(graph) => match std::fs::create_dir_all("out").and_then(|_| std::fs::write("out/day7_rules.txt", rules::write_rules(&graph))) {
+            Ok(_) => format!("Wrote {} rules to out/day7_rules.txt", graph.rule_order().len()),
+            Err(err) => format!("Couldn't write the rules: {}", err),
+        },
+        Err(message) => format!("Those rules don't work:\n{}", message),
+    };
+
+    s.add_layer(
+        Dialog::around(ScrollView::new(TextView::new(message)))
+            .title("Tidy Rules 📝")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn bag_query_prompt(s: &mut Cursive) {
+    let prompt = LinearLayout::vertical()
+        .child(TextView::new("contains? X, inside X [depth<=N], path X -> Y, total inside X, leaves"))
+        .child(EditView::new().content("contains? shiny gold").with_name("bag_query").fixed_width(40));
+
+    s.add_layer(
+        Dialog::around(prompt)
+            .title("Bag Queries ❓")
+            .button("Ask", |s| {
+                let query = s.call_on_name("bag_query", |view: &mut EditView| view.get_content()).unwrap();
+                bag_query(s, query.to_string());
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn bag_query(s: &mut Cursive, query: String) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let text = std::fs::read_to_string("inputs/day7.txt").unwrap();
+            match rules::parse_graph(&text).and_then(|graph| query::ask(&graph, &query)) {
+                Ok(answer) => Ok(answer.to_string()),
+                Err(message) => Ok(message),
+            }
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(50);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Bag Queries ❓")
+            .button("Thanks!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Command line: `day7 query [--rules FILE] <query>`.
+pub fn run_command(args: &[String]) -> Result<String, String> {
+    match args {
+        [command, rest @ ..] if command == "query" => {
+            let (rules_path, words) = match rest {
+                [flag, path, words @ ..] if flag == "--rules" => (path.as_str(), words),
+                _ => ("inputs/day7.txt", rest),
+            };
+            let text = std::fs::read_to_string(rules_path).map_err(|err| format!("Couldn't read {}: {}", rules_path, err))?;
+            let graph = rules::parse_graph(&text)?;
+            query::ask(&graph, &words.join(" ")).map(|answer| answer.to_string())
+        }
+        _ => Err("usage: day7 query [--rules FILE] <query>".to_string()),
+    }
+}
+
 pub fn find_all_kinds_of_bag(lines: &Vec<String>) -> Vec<String> {
     let mut bags: Vec<String> = Vec::new();
 
@@ -107,28 +201,41 @@ pub fn find_all_kinds_of_bag(lines: &Vec<String>) -> Vec<String> {
     bags.iter().unique().map(|bag| bag.to_string()).collect::<Vec<String>>().into_iter().filter(|bag| { bag.to_string() != "no other".to_string()}).collect()
 }
 
-pub fn find_all_rules(lines: &Vec<String>) -> HashMap<String, HashMap<String, i32>> {
+// Every malformed line is reported, as parse_rules does, along with any count too big for an i32.
+pub fn find_all_rules(lines: &Vec<String>) -> Result<HashMap<String, HashMap<String, i32>>, Vec<rules::RuleError>> {
     let mut rules_hash: HashMap<String, HashMap<String, i32>> = HashMap::new();
-
-    let bag_pattern = Regex::new(r"(\w+) (\w+) bag").unwrap();
-    let rule_pattern = Regex::new(r"([0-9]) (\w+) (\w+)").unwrap();
-
-    lines.iter().for_each(|line| {
-        let first_bag_capture = &bag_pattern.captures_iter(line).collect::<Vec<regex::Captures>>()[0];
-        let first_bag = first_bag_capture[1].to_string() + " " + &first_bag_capture[2].to_string();
-
-        rules_hash.insert(first_bag.to_string(), HashMap::new());
-        
-        for rule_capture in rule_pattern.captures_iter(line) {
-            let num_bag_type: i32 = rule_capture[1].parse().unwrap();
-            let bag_type = rule_capture[2].to_string() + " " + &rule_capture[3].to_string();
-
-            let rule_hash = rules_hash.get_mut(&first_bag).unwrap();
-            rule_hash.insert(bag_type.to_string(), num_bag_type);
+    let mut errors = Vec::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let (bag, contents) = match rules::parse_rule(line, idx + 1) {
+            Ok(Some(rule)) => rule,
+            Ok(None) => continue,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+
+        let mut rule_hash = HashMap::new();
+        for (bag_type, num_bag_type) in contents {
+            match i32::try_from(num_bag_type) {
+                Ok(num_bag_type) => {
+                    rule_hash.insert(bag_type, num_bag_type);
+                }
+                Err(_) => {
+                    let offset = line.find(&format!("{} {} bag", num_bag_type, bag_type)).unwrap_or(0);
+                    errors.push(rules::RuleError { line: idx + 1, column: line[..offset].chars().count() + 1, message: "that's too many bags".to_string() });
+                }
+            }
         }
-    });
+        rules_hash.insert(bag, rule_hash);
+    }
 
-    rules_hash
+    if errors.is_empty() {
+        Ok(rules_hash)
+    } else {
+        Err(errors)
+    }
 }
 
 pub fn get_rules_for_bag(bag: String, rules: &HashMap<String, HashMap<String, i32>>) -> HashMap<String, i32> {
@@ -201,7 +308,7 @@ mod day7tests {
             "faded blue bags contain no other bags.".to_string(),
             "dotted black bags contain no other bags.".to_string(),
         ];
-        let rules = find_all_rules(&test_data);
+        let rules = find_all_rules(&test_data).unwrap();
 
         assert_eq!(rules.len(), 9);
 
@@ -230,7 +337,7 @@ mod day7tests {
             "faded blue bags contain no other bags.".to_string(),
             "dotted black bags contain no other bags.".to_string(),
         ];
-        let rules = find_all_rules(&test_data);
+        let rules = find_all_rules(&test_data).unwrap();
         let bags_inside_light_red_bag = get_all_bags_bag_may_contain_recursive("light red".to_string(), &rules);
 
         assert_eq!(bags_inside_light_red_bag.len(), 7);
@@ -243,4 +350,31 @@ mod day7tests {
         
         assert_eq!(bags_that_shiny_gold_contains, 32);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn find_rules_handles_big_counts_and_blank_lines() {
+        let test_data = vec![
+            "light red bags contain 12 bright white bags.".to_string(),
+            "".to_string(),
+            "bright white bags contain no other bags.".to_string(),
+        ];
+        let rules = find_all_rules(&test_data).unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(count_total_bags_inside_bag_recursive("light red".to_string(), &rules), 12);
+    }
+
+    #[test]
+    fn find_rules_reports_bad_lines() {
+        let test_data = vec![
+            "light red bags contain 1 bright white bag.".to_string(),
+            "bright white bags contain 1 shiny gold".to_string(),
+            "shiny gold bags contain 3000000000 faded blue bags.".to_string(),
+        ];
+        let errors = find_all_rules(&test_data).unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "line 2, column 39: expected a full stop at the end");
+        assert_eq!(errors[1].to_string(), "line 3, column 25: that's too many bags");
+    }
+}
@@ -0,0 +1,292 @@
+// Day 7: Handy Haversacks - Queries
+// A few questions about the rules, asked as text:
+//   contains? shiny gold          bags that can end up holding shiny gold bags
+//   inside shiny gold depth<=2    bags inside shiny gold, optionally only a few levels down
+//   path light red -> faded blue  the shortest way from one bag down to another
+//   total inside dark olive       how many bags end up inside dark olive
+//   leaves                        bags that hold nothing
+use super::graph::{BagGraph, BagId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BagQuery {
+    ContainedBy(String),
+    Inside { bag: String, max_depth: Option<u32> },
+    Path { from: String, to: String },
+    TotalInside(String),
+    Leaves,
+}
+
+fn colour(words: &[&str], query: &str) -> Result<String, String> {
+    if words.is_empty() {
+        Err(format!("\"{}\" needs a bag colour", query))
+    } else {
+        Ok(words.join(" "))
+    }
+}
+
+impl FromStr for BagQuery {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<BagQuery, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["contains?", bag @ ..] => Ok(BagQuery::ContainedBy(colour(bag, "contains?")?)),
+            ["total", "inside", bag @ ..] => Ok(BagQuery::TotalInside(colour(bag, "total inside")?)),
+            ["leaves"] => Ok(BagQuery::Leaves),
+            ["inside", rest @ ..] => match rest.split_last() {
+                Some((last, bag)) if last.starts_with("depth<=") => {
+                    let depth = last["depth<=".len()..].parse::<u32>().map_err(|_| format!("{} isn't a depth", &last["depth<=".len()..]))?;
+                    Ok(BagQuery::Inside { bag: colour(bag, "inside")?, max_depth: Some(depth) })
+                }
+                _ => Ok(BagQuery::Inside { bag: colour(rest, "inside")?, max_depth: None }),
+            },
+            ["path", rest @ ..] => match rest.iter().position(|word| *word == "->") {
+                Some(arrow) => Ok(BagQuery::Path { from: colour(&rest[..arrow], "path")?, to: colour(&rest[arrow + 1..], "path ... ->")? }),
+                None => Err("path needs two bags separated by ->".to_string()),
+            },
+            _ => Err(format!("Don't know how to ask \"{}\"", text.trim())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryAnswer {
+    pub heading: String,
+    // Each bag with the count that goes with it for this query.
+    pub rows: Vec<(String, u64)>,
+}
+
+impl fmt::Display for QueryAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.heading)?;
+        for (bag, count) in &self.rows {
+            write!(f, "\n  {:>8} × {}", count, bag)?;
+        }
+        Ok(())
+    }
+}
+
+fn overflow(bag: &str) -> String {
+    format!("too many bags inside {} to count", bag)
+}
+
+// How many `target` bags end up inside `bag`.
+fn held_count(graph: &BagGraph, bag: BagId, target: BagId, memo: &mut HashMap<BagId, Option<u64>>) -> Option<u64> {
+    if let Some(count) = memo.get(&bag) {
+        return *count;
+    }
+
+    let mut total = Some(0u64);
+    for (inner, count) in graph.contents(bag) {
+        let direct = if *inner == target { 1 } else { 0 };
+        total = total.and_then(|total| {
+            let below = held_count(graph, *inner, target, memo)?;
+            total.checked_add(count.checked_mul(below.checked_add(direct)?)?)
+        });
+    }
+
+    memo.insert(bag, total);
+    total
+}
+
+fn count_paths(graph: &BagGraph, bag: BagId, target: BagId, memo: &mut HashMap<BagId, u64>) -> u64 {
+    if bag == target {
+        return 1;
+    }
+    if let Some(paths) = memo.get(&bag) {
+        return *paths;
+    }
+
+    let paths = graph.contents(bag).iter().fold(0u64, |paths, (inner, _)| paths.saturating_add(count_paths(graph, *inner, target, memo)));
+    memo.insert(bag, paths);
+    paths
+}
+
+fn sort_rows(rows: &mut [(String, u64)]) {
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+}
+
+pub fn run_query(graph: &BagGraph, query: &BagQuery) -> Result<QueryAnswer, String> {
+    let lookup = |bag: &str| graph.id(bag).map_err(|err| err.to_string());
+
+    match query {
+        BagQuery::ContainedBy(bag) => {
+            let target = lookup(bag)?;
+            let mut memo = HashMap::new();
+            let mut rows = Vec::new();
+            for &ancestor in graph.ancestors(target) {
+                let held = held_count(graph, ancestor, target, &mut memo).ok_or_else(|| overflow(graph.name(ancestor)))?;
+                rows.push((graph.name(ancestor).to_string(), held));
+            }
+            sort_rows(&mut rows);
+            Ok(QueryAnswer { heading: format!("{} bags can hold {} bags (count is how many each holds):", rows.len(), bag), rows })
+        }
+        BagQuery::Inside { bag, max_depth } => {
+            let start = lookup(bag)?;
+            let mut totals: HashMap<BagId, u64> = HashMap::new();
+            let mut level: HashMap<BagId, u64> = HashMap::new();
+            level.insert(start, 1);
+
+            let mut depth = 0;
+            while !level.is_empty() && max_depth.is_none_or(|max_depth| depth < max_depth) {
+                let mut next_level: HashMap<BagId, u64> = HashMap::new();
+                for (outer, outer_count) in &level {
+                    for (inner, count) in graph.contents(*outer) {
+                        let added = outer_count.checked_mul(*count).ok_or_else(|| overflow(bag))?;
+                        let entry = next_level.entry(*inner).or_insert(0);
+                        *entry = entry.checked_add(added).ok_or_else(|| overflow(bag))?;
+                    }
+                }
+                for (inner, count) in &next_level {
+                    let entry = totals.entry(*inner).or_insert(0);
+                    *entry = entry.checked_add(*count).ok_or_else(|| overflow(bag))?;
+                }
+                level = next_level;
+                depth += 1;
+            }
+
+            let mut rows: Vec<(String, u64)> = totals.into_iter().map(|(inner, count)| (graph.name(inner).to_string(), count)).collect();
+            sort_rows(&mut rows);
+            let limit = match max_depth {
+                Some(max_depth) => format!(" within {} levels", max_depth),
+                None => String::new(),
+            };
+            Ok(QueryAnswer { heading: format!("{} kinds of bag inside {}{}:", rows.len(), bag, limit), rows })
+        }
+        BagQuery::Path { from, to } => {
+            let (start, target) = (lookup(from)?, lookup(to)?);
+
+            // Breadth first, so the first time we reach the target is along a shortest path.
+            let mut parents: HashMap<BagId, (BagId, u64)> = HashMap::new();
+            let mut queue = VecDeque::from(vec![start]);
+            while let Some(bag) = queue.pop_front() {
+                if bag == target {
+                    break;
+                }
+                for (inner, count) in graph.contents(bag) {
+                    if *inner != start && !parents.contains_key(inner) {
+                        parents.insert(*inner, (bag, *count));
+                        queue.push_back(*inner);
+                    }
+                }
+            }
+
+            if start != target && !parents.contains_key(&target) {
+                return Ok(QueryAnswer { heading: format!("{} bags can't hold {} bags.", from, to), rows: Vec::new() });
+            }
+
+            let mut rows = Vec::new();
+            let mut bag = target;
+            while bag != start {
+                let (parent, count) = parents[&bag];
+                rows.push((graph.name(bag).to_string(), count));
+                bag = parent;
+            }
+            rows.push((from.to_string(), 1));
+            rows.reverse();
+
+            let paths = count_paths(graph, start, target, &mut HashMap::new());
+            Ok(QueryAnswer { heading: format!("{} from {} to {}, the shortest being (count is per bag above):", if paths == 1 { "1 path".to_string() } else { format!("{} paths", paths) }, from, to), rows })
+        }
+        BagQuery::TotalInside(bag) => {
+            let total = graph.total_inside_of(bag).map_err(|err| err.to_string())?;
+            Ok(QueryAnswer { heading: format!("{} bags end up inside {}.", total, bag), rows: vec![(bag.to_string(), total)] })
+        }
+        BagQuery::Leaves => {
+            let mut rows: Vec<(String, u64)> = (0..graph.len())
+                .filter(|bag| graph.contents(*bag).is_empty())
+                .map(|bag| (graph.name(bag).to_string(), graph.containers(bag).len() as u64))
+                .collect();
+            sort_rows(&mut rows);
+            Ok(QueryAnswer { heading: format!("{} bags hold nothing (count is how many kinds hold them directly):", rows.len()), rows })
+        }
+    }
+}
+
+pub fn ask(graph: &BagGraph, query: &str) -> Result<QueryAnswer, String> {
+    run_query(graph, &query.parse()?)
+}
+
+#[cfg(test)]
+mod querytests {
+    use super::*;
+    use crate::day7::rules::parse_graph;
+
+    fn get_test_graph() -> BagGraph {
+        parse_graph(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn queries_parse() {
+        assert_eq!("contains? shiny gold".parse(), Ok(BagQuery::ContainedBy("shiny gold".to_string())));
+        assert_eq!("inside shiny gold depth<=2".parse(), Ok(BagQuery::Inside { bag: "shiny gold".to_string(), max_depth: Some(2) }));
+        assert_eq!("inside shiny gold".parse(), Ok(BagQuery::Inside { bag: "shiny gold".to_string(), max_depth: None }));
+        assert_eq!("path light red -> faded blue".parse(), Ok(BagQuery::Path { from: "light red".to_string(), to: "faded blue".to_string() }));
+        assert_eq!("total inside dark olive".parse(), Ok(BagQuery::TotalInside("dark olive".to_string())));
+        assert_eq!(" leaves ".parse(), Ok(BagQuery::Leaves));
+
+        assert!("contains?".parse::<BagQuery>().is_err());
+        assert!("inside shiny gold depth<=two".parse::<BagQuery>().is_err());
+        assert!("path light red".parse::<BagQuery>().is_err());
+        assert!("what's in the bag".parse::<BagQuery>().is_err());
+    }
+
+    #[test]
+    fn contains_lists_holders_with_counts() {
+        let answer = ask(&get_test_graph(), "contains? shiny gold").unwrap();
+
+        assert_eq!(answer.rows.len(), 4);
+        assert_eq!(answer.rows[0], ("dark orange".to_string(), 3 + 4 * 2));
+        assert!(answer.rows.contains(&("bright white".to_string(), 1)));
+        assert!(answer.rows.contains(&("light red".to_string(), 1 + 2 * 2)));
+    }
+
+    #[test]
+    fn inside_respects_depth() {
+        let graph = get_test_graph();
+
+        let shallow = ask(&graph, "inside shiny gold depth<=1").unwrap();
+        assert_eq!(shallow.rows, vec![("vibrant plum".to_string(), 2), ("dark olive".to_string(), 1)]);
+
+        let deep = ask(&graph, "inside shiny gold depth<=2").unwrap();
+        assert_eq!(deep.rows.len(), 4);
+        assert!(deep.rows.contains(&("faded blue".to_string(), 3 + 2 * 5)));
+        assert_eq!(deep.rows.iter().map(|(_, count)| count).sum::<u64>(), 32);
+        assert_eq!(ask(&graph, "inside shiny gold").unwrap().rows, deep.rows);
+    }
+
+    #[test]
+    fn path_finds_shortest_route() {
+        let graph = get_test_graph();
+        let answer = ask(&graph, "path light red -> faded blue").unwrap();
+
+        assert_eq!(answer.rows, vec![("light red".to_string(), 1), ("muted yellow".to_string(), 2), ("faded blue".to_string(), 9)]);
+        assert!(answer.heading.starts_with("5 paths from light red"));
+        assert!(ask(&graph, "path faded blue -> light red").unwrap().rows.is_empty());
+        assert!(ask(&graph, "path light red -> plaid purple").is_err());
+    }
+
+    #[test]
+    fn totals_and_leaves_work() {
+        let graph = get_test_graph();
+
+        assert_eq!(ask(&graph, "total inside dark olive").unwrap().rows, vec![("dark olive".to_string(), 7)]);
+        assert_eq!(ask(&graph, "leaves").unwrap().rows, vec![("faded blue".to_string(), 3), ("dotted black".to_string(), 2)]);
+        assert_eq!(ask(&graph, "leaves").unwrap().to_string().lines().count(), 3);
+    }
+}
@@ -0,0 +1,202 @@
+// Day 7: Handy Haversacks - Rule Text
+// Parses "X bags contain N Y bags, M Z bags." lines, and writes them back out.
+// Colours can be any number of words and counts any number of digits.
+use super::graph::BagGraph;
+use std::fmt;
+
+pub type Rule = (String, Vec<(String, u64)>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleError {
+    // Both 1-based, with the column counted in characters.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+const CONTAIN: &str = " bags contain ";
+const NOTHING: &str = "no other bags";
+
+fn error_at(line: &str, line_no: usize, byte_offset: usize, message: &str) -> RuleError {
+    RuleError { line: line_no, column: line[..byte_offset].chars().count() + 1, message: message.to_string() }
+}
+
+fn parse_contents_item(line: &str, line_no: usize, item: &str, offset: usize) -> Result<(String, u64), RuleError> {
+    let digits = item.len() - item.trim_start_matches(|chr: char| chr.is_ascii_digit()).len();
+    if digits == 0 {
+        return Err(error_at(line, line_no, offset, "expected a number of bags"));
+    }
+
+    let count: u64 = item[..digits].parse().map_err(|_| error_at(line, line_no, offset, "that's too many bags"))?;
+
+    let rest = &item[digits..];
+    let colour_offset = offset + digits + 1;
+    if !rest.starts_with(' ') {
+        return Err(error_at(line, line_no, offset + digits, "expected a space after the number"));
+    }
+
+    let colour = match rest[1..].strip_suffix(" bags").or_else(|| rest[1..].strip_suffix(" bag")) {
+        Some(colour) => colour,
+        None => return Err(error_at(line, line_no, colour_offset, "expected a colour followed by \"bag\" or \"bags\"")),
+    };
+
+    if colour.trim().is_empty() || colour.trim() != colour || colour.contains("  ") {
+        return Err(error_at(line, line_no, colour_offset, "expected a colour"));
+    }
+
+    Ok((colour.to_string(), count))
+}
+
+// Blank lines parse to None.
+pub fn parse_rule(line: &str, line_no: usize) -> Result<Option<Rule>, RuleError> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let start = line.len() - line.trim_start().len();
+    let body = line.trim();
+
+    let split = match body.find(CONTAIN) {
+        Some(split) if split > 0 => split,
+        Some(_) => return Err(error_at(line, line_no, start, "expected a colour before \"bags contain\"")),
+        None => return Err(error_at(line, line_no, start, "expected \"<colour> bags contain ...\"")),
+    };
+    let container = &body[..split];
+
+    let list_offset = start + split + CONTAIN.len();
+    let list = match body[split + CONTAIN.len()..].strip_suffix('.') {
+        Some(list) => list,
+        None => return Err(error_at(line, line_no, start + body.len(), "expected a full stop at the end")),
+    };
+
+    if list == NOTHING {
+        return Ok(Some((container.to_string(), Vec::new())));
+    }
+
+    let mut contents = Vec::new();
+    let mut item_offset = list_offset;
+    for item in list.split(", ") {
+        let (colour, count) = parse_contents_item(line, line_no, item, item_offset)?;
+        if contents.iter().any(|(existing, _): &(String, u64)| *existing == colour) {
+            return Err(error_at(line, line_no, item_offset, &format!("{} bags are listed twice", colour)));
+        }
+        contents.push((colour, count));
+        item_offset += item.len() + 2;
+    }
+
+    Ok(Some((container.to_string(), contents)))
+}
+
+// Every malformed line is reported, not just the first.
+pub fn parse_rules(text: &str) -> Result<Vec<Rule>, Vec<RuleError>> {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        match parse_rule(line, idx + 1) {
+            Ok(Some(rule)) => rules.push(rule),
+            Ok(None) => {}
+            Err(err) => errors.push(err),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(rules)
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn parse_graph(text: &str) -> Result<BagGraph, String> {
+    let rules = parse_rules(text).map_err(|errors| errors.iter().map(|err| err.to_string()).collect::<Vec<String>>().join("\n"))?;
+    BagGraph::build(rules).map_err(|err| err.to_string())
+}
+
+fn describe_contents(graph: &BagGraph, contents: &[(usize, u64)]) -> String {
+    if contents.is_empty() {
+        return NOTHING.to_string();
+    }
+
+    contents
+        .iter()
+        .map(|(bag, count)| format!("{} {} {}", count, graph.name(*bag), if *count == 1 { "bag" } else { "bags" }))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// One line per rule, in the order the rules were read. Bags that only ever appear
+// inside others don't get a line of their own.
+pub fn write_rules(graph: &BagGraph) -> String {
+    graph
+        .rule_order()
+        .iter()
+        .map(|bag| format!("{}{}{}.\n", graph.name(*bag), CONTAIN, describe_contents(graph, graph.contents(*bag))))
+        .collect()
+}
+
+#[cfg(test)]
+mod rulestests {
+    use super::*;
+
+    #[test]
+    fn rules_parse() {
+        assert_eq!(
+            parse_rule("light red bags contain 1 bright white bag, 12 muted yellow bags.", 1),
+            Ok(Some(("light red".to_string(), vec![("bright white".to_string(), 1), ("muted yellow".to_string(), 12)])))
+        );
+        assert_eq!(parse_rule("faded blue bags contain no other bags.", 1), Ok(Some(("faded blue".to_string(), vec![]))));
+        assert_eq!(
+            parse_rule("  very dark mauve bags contain 100 pale blue green bags.", 1),
+            Ok(Some(("very dark mauve".to_string(), vec![("pale blue green".to_string(), 100)])))
+        );
+        assert_eq!(parse_rule("   ", 1), Ok(None));
+    }
+
+    #[test]
+    fn malformed_rules_have_positions() {
+        let error = |line: &str| parse_rule(line, 7).unwrap_err();
+
+        assert_eq!(error("light red bags hold 1 bright white bag."), RuleError { line: 7, column: 1, message: "expected \"<colour> bags contain ...\"".to_string() });
+        assert_eq!(error("light red bags contain 1 bright white bag").column, 42);
+        assert_eq!(error("light red bags contain one bright white bag.").column, 24);
+        assert_eq!(error("light red bags contain 1 bright white bag, 2 muted yellow.").column, 46);
+        assert_eq!(error("light red bags contain 2bright white bags.").column, 25);
+        assert_eq!(error("light red bags contain 1 red bag, 2 red bags.").message, "red bags are listed twice");
+        assert_eq!(error(" bags contain no other bags.").column, 2);
+        assert_eq!(error("é bags contain 1 x bag, oops.").column, 25);
+        assert_eq!(error("a bags contain 99999999999999999999 b bags.").message, "that's too many bags");
+    }
+
+    #[test]
+    fn every_bad_line_is_reported() {
+        let text = "a bags contain 1 b bag.\n\nb bags contain nothing.\nc bags contain no other bags\n";
+        let errors = parse_rules(text).unwrap_err();
+
+        assert_eq!(errors.iter().map(|err| err.line).collect::<Vec<usize>>(), vec![3, 4]);
+        assert_eq!(errors[1].to_string(), "line 4, column 29: expected a full stop at the end");
+    }
+
+    #[test]
+    fn writer_round_trips() {
+        let text = "a bags contain 1 b bag, 20 c d bags.\nb bags contain no other bags.\n\nc d bags contain 3 b bags.\n";
+        let graph = parse_graph(text).unwrap();
+
+        assert_eq!(write_rules(&graph), text.replace("\n\n", "\n"));
+        assert_eq!(graph.total_inside_of("a"), Ok(1 + 20 + 20 * 3));
+
+        let input = std::fs::read_to_string("inputs/day7.txt").unwrap();
+        assert_eq!(write_rules(&parse_graph(&input).unwrap()).trim_end(), input.trim_end());
+    }
+
+    #[test]
+    fn graph_errors_come_through() {
+        assert_eq!(parse_graph("a bags contain 1 a bag.").unwrap_err(), "bags contain themselves: a -> a");
+        assert!(parse_graph("a bags contain 1 b bag.\nb bags contain 2 bad.").unwrap_err().starts_with("line 2"));
+    }
+}
@@ -0,0 +1,286 @@
+// Day 8: Handheld Halting - Assembler
+// Turns hand-written programs into the puzzle's encoding. On top of plain `op ±n` lines:
+//   # or ; starts a comment
+//   blank lines are skipped
+//   `name:` at the start of a line labels the next instruction
+//   jumps can name a label instead of an offset
+// Which mnemonics exist, what operands they take and which Instruction they become comes
+// from an InstructionSet. That table only covers decoding. Every entry builds one of the
+// existing Instruction variants, and what those do is spelled out in ProgramState::step,
+// CompiledProgram and the cfg and repair analyses. Registering a mnemonic can rename or
+// hide an operation, but a brand new one still needs a variant and an arm in each of those.
+use super::program::{Instruction, Program};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperandKind {
+    None,
+    Number,
+    // A relative offset, or a label to work one out from.
+    Target,
+}
+
+// How to decode one mnemonic. There's nothing here about running it.
+#[derive(Clone, Copy)]
+pub struct OpcodeSpec {
+    pub mnemonic: &'static str,
+    pub operand: OperandKind,
+    // Which Instruction the mnemonic stands for. Operand-less operations get 0.
+    pub build: fn(i32) -> Instruction,
+}
+
+#[derive(Clone, Default)]
+pub struct InstructionSet {
+    specs: Vec<OpcodeSpec>,
+}
+
+impl InstructionSet {
+    pub fn new() -> InstructionSet {
+        InstructionSet::default()
+    }
+
+    // Makes the mnemonic decodable, into whichever existing Instruction spec.build gives.
+    pub fn register(&mut self, spec: OpcodeSpec) -> Result<(), String> {
+        if self.lookup(spec.mnemonic).is_some() {
+            return Err(format!("{} is already registered", spec.mnemonic));
+        }
+        self.specs.push(spec);
+        Ok(())
+    }
+
+    pub fn with(mut self, mnemonic: &'static str, operand: OperandKind, build: fn(i32) -> Instruction) -> InstructionSet {
+        self.register(OpcodeSpec { mnemonic, operand, build }).unwrap();
+        self
+    }
+
+    // Just what the puzzle's handheld understands.
+    pub fn puzzle() -> InstructionSet {
+        InstructionSet::new()
+            .with("acc", OperandKind::Number, Instruction::Acc)
+            .with("jmp", OperandKind::Target, Instruction::Jmp)
+            .with("nop", OperandKind::Number, Instruction::Nop)
+    }
+
+    pub fn extended() -> InstructionSet {
+        InstructionSet::puzzle()
+            .with("mul", OperandKind::Number, Instruction::Mul)
+            .with("jz", OperandKind::Target, Instruction::Jz)
+            .with("out", OperandKind::None, |_| Instruction::Out)
+            .with("hlt", OperandKind::None, |_| Instruction::Hlt)
+    }
+
+    // Shared copies, for decoding without building a table each time.
+    pub fn shared_puzzle() -> &'static InstructionSet {
+        static PUZZLE: OnceLock<InstructionSet> = OnceLock::new();
+        PUZZLE.get_or_init(InstructionSet::puzzle)
+    }
+
+    pub fn shared_extended() -> &'static InstructionSet {
+        static EXTENDED: OnceLock<InstructionSet> = OnceLock::new();
+        EXTENDED.get_or_init(InstructionSet::extended)
+    }
+
+    pub fn lookup(&self, mnemonic: &str) -> Option<&OpcodeSpec> {
+        self.specs.iter().find(|spec| spec.mnemonic == mnemonic)
+    }
+
+    pub fn mnemonics(&self) -> Vec<&'static str> {
+        self.specs.iter().map(|spec| spec.mnemonic).collect()
+    }
+
+    // A single line in the puzzle's encoding: no labels or comments.
+    pub fn decode(&self, line: &str) -> Result<Instruction, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let spec = match words.first() {
+            Some(mnemonic) => self.lookup(mnemonic).ok_or_else(|| format!("{} isn't an operation", mnemonic))?,
+            None => return Err("there's no operation".to_string()),
+        };
+
+        match (spec.operand, &words[1..]) {
+            (OperandKind::None, []) => Ok((spec.build)(0)),
+            (OperandKind::None, _) => Err(format!("{} doesn't take an operand", spec.mnemonic)),
+            (_, [operand]) => Ok((spec.build)(parse_number(operand)?)),
+            _ => Err(format!("\"{}\" should be an operation and a number", line.trim())),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<i32, String> {
+    text.parse().map_err(|_| format!("{} isn't a number", text))
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|chr| chr.is_ascii_alphabetic() || chr == '_') && chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_')
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+struct PendingInstruction<'a> {
+    line: usize,
+    spec: &'a OpcodeSpec,
+    operand: Option<&'a str>,
+}
+
+// Every error in the source is reported, not just the first.
+pub fn assemble(source: &str, isa: &InstructionSet) -> Result<Program, Vec<AssemblyError>> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut pending: Vec<PendingInstruction> = Vec::new();
+    let mut errors = Vec::new();
+
+    // First pass: strip comments, note where labels point, check operations and operand counts.
+    for (idx, raw_line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut line = raw_line.split(['#', ';']).next().unwrap().trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                errors.push(AssemblyError { line: line_no, message: format!("\"{}\" isn't a valid label", label) });
+                continue;
+            }
+            if labels.insert(label, pending.len()).is_some() {
+                errors.push(AssemblyError { line: line_no, message: format!("{} is already defined", label) });
+            }
+            line = rest.trim();
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (mnemonic, operands) = match words.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+
+        let spec = match isa.lookup(mnemonic) {
+            Some(spec) => spec,
+            None => {
+                errors.push(AssemblyError { line: line_no, message: format!("{} isn't an operation (expected one of {})", mnemonic, isa.mnemonics().join(", ")) });
+                continue;
+            }
+        };
+
+        match (spec.operand, operands) {
+            (OperandKind::None, []) => pending.push(PendingInstruction { line: line_no, spec, operand: None }),
+            (OperandKind::None, _) => errors.push(AssemblyError { line: line_no, message: format!("{} doesn't take an operand", mnemonic) }),
+            (_, [operand]) => pending.push(PendingInstruction { line: line_no, spec, operand: Some(operand) }),
+            _ => errors.push(AssemblyError { line: line_no, message: format!("{} takes exactly one operand", mnemonic) }),
+        }
+    }
+
+    // Second pass: resolve operands now every label is known.
+    let mut instructions = Vec::new();
+    for (address, instruction) in pending.iter().enumerate() {
+        let operand = match (instruction.spec.operand, instruction.operand) {
+            (_, None) => Ok(0),
+            (OperandKind::Target, Some(operand)) if is_label(operand) => match labels.get(operand) {
+                Some(target) => Ok(*target as i32 - address as i32),
+                None => Err(format!("{} isn't defined", operand)),
+            },
+            (_, Some(operand)) => parse_number(operand),
+        };
+
+        match operand {
+            Ok(operand) => instructions.push((instruction.spec.build)(operand)),
+            Err(message) => errors.push(AssemblyError { line: instruction.line, message }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Program::new(instructions))
+    } else {
+        errors.sort_by_key(|err| err.line);
+        Err(errors)
+    }
+}
+
+// Hand-written source, so the extensions are allowed.
+pub fn assemble_text(source: &str) -> Result<Program, String> {
+    assemble(source, InstructionSet::shared_extended()).map_err(|errors| errors.iter().map(|err| err.to_string()).collect::<Vec<String>>().join("\n"))
+}
+
+#[cfg(test)]
+mod assemblertests {
+    use super::*;
+    use crate::day8::program::TerminationReason;
+    use crate::day8::{run_program_until_terminated, ProgramState};
+
+    #[test]
+    fn plain_programs_assemble_to_the_same_encoding() {
+        let source = std::fs::read_to_string("inputs/day8.txt").unwrap();
+        let program = assemble(&source, &InstructionSet::puzzle()).unwrap();
+
+        assert_eq!(program.to_lines().join("\n"), source.trim_end());
+    }
+
+    #[test]
+    fn labels_and_comments_resolve() {
+        let source = "
+            # add three, then stop once that's been taken away again
+            acc +3
+            jz done   ; not taken, we've got three
+            acc -3
+            jz done   ; taken this time
+
+            acc +100
+            done:
+            hlt
+        ";
+        let program = assemble_text(source).unwrap();
+
+        assert_eq!(program.to_lines(), vec!["acc +3", "jz +4", "acc -3", "jz +2", "acc +100", "hlt"]);
+
+        let state = run_program_until_terminated(&ProgramState::new(), &program);
+        assert_eq!(state.termination, Some(TerminationReason::Halted));
+        assert_eq!(state.accumulator, 0);
+
+        assert_eq!(assemble_text("start: nop +0\njmp start").unwrap().to_lines(), vec!["nop +0", "jmp -1"]);
+    }
+
+    #[test]
+    fn extended_operations_run() {
+        let program = assemble_text("acc +2\nmul +21\nout\nacc -42\njz end\nacc +1\nend: out").unwrap();
+        let state = run_program_until_terminated(&ProgramState::new(), &program);
+
+        assert_eq!(state.output, vec![42, 0]);
+        assert_eq!(state.termination, Some(TerminationReason::Finished));
+    }
+
+    #[test]
+    fn bad_source_is_reported() {
+        let errors = assemble("acc +1\nfoo +2\njmp nowhere\nhlt +1\nx y: nop +0\nacc\nl: nop +0\nl: nop +0", InstructionSet::shared_extended()).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|err| err.line).collect();
+
+        assert_eq!(lines, vec![2, 3, 4, 5, 6, 8]);
+        assert!(errors[0].message.starts_with("foo isn't an operation"));
+        assert_eq!(errors[1].to_string(), "line 3: nowhere isn't defined");
+
+        // Labels only work where the operation takes a jump target.
+        assert!(assemble_text("top: acc top").is_err());
+        // The puzzle's own set doesn't know the extensions.
+        assert!(assemble("mul +2", &InstructionSet::puzzle()).is_err());
+    }
+
+    #[test]
+    fn mnemonics_can_be_added() {
+        let mut isa = InstructionSet::puzzle();
+        assert!(isa.register(OpcodeSpec { mnemonic: "acc", operand: OperandKind::Number, build: Instruction::Acc }).is_err());
+
+        // A new name for something step already knows how to run.
+        isa.register(OpcodeSpec { mnemonic: "dbl", operand: OperandKind::None, build: |_| Instruction::Mul(2) }).unwrap();
+        let program = assemble("acc +5\ndbl", &isa).unwrap();
+
+        assert_eq!(program.instructions(), &[Instruction::Acc(5), Instruction::Mul(2)]);
+    }
+}
@@ -0,0 +1,340 @@
+// Day 8: Handheld Halting - Control Flow
+// Works out what a program does from its shape alone, without touching the accumulator:
+// which instructions can ever run, where it loops (and which jump sends it back round),
+// and which jumps leave the program altogether.
+use super::program::{Instruction, Program, TerminationReason};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Instruction(usize),
+    // Just past the last instruction, where the program finishes.
+    End,
+    Outside(i64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Loop {
+    // Where the loop starts again.
+    pub head: usize,
+    // The instruction that sends control back to the head.
+    pub closing: usize,
+    // Every instruction round the loop, starting from the head.
+    pub members: Vec<usize>,
+    // Whether a conditional jump in the loop can get out of it.
+    pub can_exit: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaticOutcome {
+    pub termination: TerminationReason,
+    pub program_counter: i32,
+    pub last_instruction: Option<(i32, Instruction)>,
+    // The instructions that run, in order.
+    pub path: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlFlowGraph {
+    instructions: Vec<Instruction>,
+    successors: Vec<Vec<Target>>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(program: &Program) -> ControlFlowGraph {
+        let len = program.len() as i64;
+        let target = |address: i64| {
+            if address == len {
+                Target::End
+            } else if address < 0 || address > len {
+                Target::Outside(address)
+            } else {
+                Target::Instruction(address as usize)
+            }
+        };
+
+        let successors = program
+            .instructions()
+            .iter()
+            .enumerate()
+            .map(|(idx, instruction)| {
+                let idx = idx as i64;
+                match instruction {
+                    Instruction::Jmp(operand) => vec![target(idx + *operand as i64)],
+                    // Taken first, then falling through.
+                    Instruction::Jz(operand) => vec![target(idx + *operand as i64), target(idx + 1)],
+                    Instruction::Hlt => vec![],
+                    _ => vec![target(idx + 1)],
+                }
+            })
+            .collect();
+
+        ControlFlowGraph { instructions: program.instructions().to_vec(), successors }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn successors(&self, idx: usize) -> &[Target] {
+        &self.successors[idx]
+    }
+
+    // Everything that can run when starting from the first instruction.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.len()];
+        let mut stack = Vec::new();
+        if !self.is_empty() {
+            reachable[0] = true;
+            stack.push(0);
+        }
+
+        while let Some(idx) = stack.pop() {
+            for target in &self.successors[idx] {
+                if let Target::Instruction(next) = *target {
+                    if !reachable[next] {
+                        reachable[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+
+        reachable
+    }
+
+    pub fn unreachable(&self) -> Vec<usize> {
+        self.reachable().iter().enumerate().filter(|(_, reachable)| !**reachable).map(|(idx, _)| idx).collect()
+    }
+
+    // Every jump that would leave the program, as (instruction, where it lands).
+    pub fn out_of_range_jumps(&self) -> Vec<(usize, i64)> {
+        self.successors
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, targets)| {
+                targets.iter().filter_map(move |target| match target {
+                    Target::Outside(address) => Some((idx, *address)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    // Loops reachable from the start, one per back edge found by a depth-first search.
+    pub fn loops(&self) -> Vec<Loop> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        // 0 unvisited, 1 on the current path, 2 done.
+        let mut state = vec![0u8; self.len()];
+        let mut path: Vec<(usize, usize)> = vec![(0, 0)];
+        let mut loops = Vec::new();
+        state[0] = 1;
+
+        while let Some(&(idx, edge)) = path.last() {
+            path.last_mut().unwrap().1 += 1;
+
+            match self.successors[idx].get(edge) {
+                Some(Target::Instruction(next)) => match state[*next] {
+                    0 => {
+                        state[*next] = 1;
+                        path.push((*next, 0));
+                    }
+                    1 => {
+                        let start = path.iter().position(|(member, _)| member == next).unwrap();
+                        let members: Vec<usize> = path[start..].iter().map(|(member, _)| *member).collect();
+                        let can_exit = members.iter().any(|member| {
+                            self.successors[*member].iter().any(|target| match target {
+                                Target::Instruction(to) => !members.contains(to),
+                                _ => true,
+                            })
+                        });
+                        loops.push(Loop { head: *next, closing: idx, members, can_exit });
+                    }
+                    _ => {}
+                },
+                Some(_) => {}
+                None => {
+                    state[idx] = 2;
+                    path.pop();
+                }
+            }
+        }
+
+        loops
+    }
+
+    // Follows the only way through the program. None when a conditional jump means
+    // the route depends on the accumulator.
+    pub fn predict(&self) -> Option<StaticOutcome> {
+        let mut seen = vec![false; self.len()];
+        let mut path = Vec::new();
+        let mut last_instruction = None;
+        let mut pc = Target::Instruction(0);
+        if self.is_empty() {
+            pc = Target::End;
+        }
+
+        loop {
+            let idx = match pc {
+                Target::Instruction(idx) => idx,
+                Target::End => return Some(StaticOutcome { termination: TerminationReason::Finished, program_counter: self.len() as i32, last_instruction, path }),
+                Target::Outside(address) => return Some(StaticOutcome { termination: TerminationReason::OutOfBounds, program_counter: address as i32, last_instruction, path }),
+            };
+            if seen[idx] {
+                return Some(StaticOutcome { termination: TerminationReason::Looped, program_counter: idx as i32, last_instruction, path });
+            }
+            seen[idx] = true;
+            path.push(idx);
+            last_instruction = Some((idx as i32, self.instructions[idx]));
+
+            match self.successors[idx].as_slice() {
+                [] => return Some(StaticOutcome { termination: TerminationReason::Halted, program_counter: idx as i32, last_instruction, path }),
+                [next] => pc = *next,
+                _ => return None,
+            }
+        }
+    }
+
+    // Graphviz, with unreachable instructions greyed out and loop-closing jumps in red.
+    pub fn to_dot(&self) -> String {
+        let reachable = self.reachable();
+        let closing: Vec<(usize, usize)> = self.loops().iter().map(|found| (found.closing, found.head)).collect();
+        let mut dot = String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+
+        for (idx, instruction) in self.instructions.iter().enumerate() {
+            let style = if reachable[idx] { "" } else { ", style=dashed, fontcolor=grey" };
+            writeln!(dot, "    n{} [label=\"{}: {}\"{}];", idx, idx, instruction, style).unwrap();
+        }
+        dot.push_str("    end [label=\"end\", shape=doublecircle];\n");
+
+        let mut outside = Vec::new();
+        for (idx, targets) in self.successors.iter().enumerate() {
+            for target in targets {
+                let (name, style) = match target {
+                    Target::Instruction(next) if closing.contains(&(idx, *next)) => (format!("n{}", next), " [color=red]"),
+                    Target::Instruction(next) => (format!("n{}", next), ""),
+                    Target::End => ("end".to_string(), ""),
+                    Target::Outside(address) => {
+                        if !outside.contains(address) {
+                            outside.push(*address);
+                        }
+                        (format!("out{}", address.to_string().replace('-', "_")), " [color=orange]")
+                    }
+                };
+                writeln!(dot, "    n{} -> {}{};", idx, name, style).unwrap();
+            }
+        }
+        for address in outside {
+            writeln!(dot, "    out{} [label=\"{}?\", shape=octagon];", address.to_string().replace('-', "_"), address).unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+
+        let unreachable = self.unreachable();
+        lines.push(format!("{} of {} instructions can run", self.len() - unreachable.len(), self.len()));
+        if !unreachable.is_empty() {
+            lines.push(format!("Never run: {}", unreachable.iter().map(|idx| idx.to_string()).collect::<Vec<String>>().join(", ")));
+        }
+
+        for found in self.loops() {
+            let kind = if found.can_exit { "loop" } else { "infinite loop" };
+            lines.push(format!("{} of {} from {}, closed by {}: {}", kind, found.members.len(), found.head, found.closing, self.instructions[found.closing]));
+        }
+
+        for (idx, address) in self.out_of_range_jumps() {
+            lines.push(format!("{}: {} jumps to {}, outside the program", idx, self.instructions[idx], address));
+        }
+
+        match self.predict() {
+            Some(outcome) => lines.push(format!("It {} after {} instructions", outcome.termination, outcome.path.len())),
+            None => lines.push("Where it goes depends on the accumulator".to_string()),
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod cfgtests {
+    use super::*;
+    use crate::day8::assembler::InstructionSet;
+    use crate::day8::{load_program, run_program_until_terminated, ProgramState};
+
+    fn decode(lines: &[&str]) -> Program {
+        Program::decode_with(&lines.iter().map(|line| line.to_string()).collect::<Vec<String>>(), &InstructionSet::extended()).unwrap()
+    }
+
+    fn graph(lines: &[&str]) -> ControlFlowGraph {
+        ControlFlowGraph::new(&decode(lines))
+    }
+
+    fn assert_matches_run(program: &Program) {
+        let outcome = ControlFlowGraph::new(program).predict().unwrap();
+        let state = run_program_until_terminated(&ProgramState::new(), program);
+
+        assert_eq!(Some(outcome.termination), state.termination);
+        assert_eq!(outcome.program_counter, state.program_counter);
+        assert_eq!(outcome.last_instruction, state.last_instruction);
+        let mut ran = outcome.path.clone();
+        ran.sort_unstable();
+        assert_eq!(ran, state.visited.indices());
+    }
+
+    #[test]
+    fn puzzle_example_loops() {
+        let cfg = graph(&["nop +0", "acc +1", "jmp +4", "acc +3", "jmp -3", "acc -99", "acc +1", "jmp -4", "acc +6"]);
+
+        assert_eq!(cfg.unreachable(), vec![5, 8]);
+        assert_eq!(cfg.loops(), vec![Loop { head: 1, closing: 4, members: vec![1, 2, 6, 7, 3, 4], can_exit: false }]);
+        assert!(cfg.out_of_range_jumps().is_empty());
+
+        let outcome = cfg.predict().unwrap();
+        assert_eq!(outcome.termination, TerminationReason::Looped);
+        assert_eq!(outcome.last_instruction, Some((4, Instruction::Jmp(-3))));
+    }
+
+    #[test]
+    fn out_of_range_and_conditional_jumps() {
+        let cfg = graph(&["nop +0", "jmp +5", "jmp -3"]);
+        assert_eq!(cfg.out_of_range_jumps(), vec![(1, 6), (2, -1)]);
+        assert_eq!(cfg.unreachable(), vec![2]);
+        assert_eq!(cfg.predict().unwrap().termination, TerminationReason::OutOfBounds);
+
+        let cfg = graph(&["acc +1", "jz +2", "jmp -2", "hlt"]);
+        assert_eq!(cfg.successors(1), &[Target::Instruction(3), Target::Instruction(2)]);
+        assert!(cfg.loops()[0].can_exit);
+        assert_eq!(cfg.predict(), None);
+    }
+
+    #[test]
+    fn predictions_match_running() {
+        assert_matches_run(&load_program("inputs/day8.txt").unwrap());
+        for lines in [&["acc +1", "nop +0", "jmp -2"][..], &["nop +0", "jmp +5"], &["acc +3", "hlt", "acc +1"], &["jmp +2", "acc +1", "acc +2"], &[]] {
+            assert_matches_run(&decode(lines));
+        }
+    }
+
+    #[test]
+    fn dot_export() {
+        let dot = graph(&["acc +1", "jmp -1", "jmp +9"]).to_dot();
+
+        assert!(dot.starts_with("digraph program {"));
+        assert!(dot.contains("n0 [label=\"0: acc +1\"];"));
+        assert!(dot.contains("n1 -> n0 [color=red];"));
+        assert!(dot.contains("n2 [label=\"2: jmp +9\", style=dashed, fontcolor=grey];"));
+        assert!(dot.contains("n2 -> out11 [color=orange];"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+}
@@ -0,0 +1,238 @@
+// Day 8: Handheld Halting - Compiled Engine
+// A second way to run programs, for big generated ones. Jumps are resolved to absolute
+// addresses up front and the run keeps its registers in locals, only building the
+// ProgramState at the end, so each step is a bounds check, a flag and one small match.
+use super::program::{Instruction, Program, TerminationReason};
+use super::ProgramState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Acc(i32),
+    // Where control goes next; jmp and nop both become one of these.
+    Goto(i64),
+    Mul(i32),
+    // Taken when the accumulator is zero, otherwise falls through.
+    Jz(i64),
+    Out,
+    Hlt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledProgram {
+    ops: Vec<Op>,
+    // Kept to fill in last_instruction the same way the interpreter does.
+    source: Vec<Instruction>,
+    branches_on_accumulator: bool,
+}
+
+impl CompiledProgram {
+    pub fn new(program: &Program) -> CompiledProgram {
+        let ops = program
+            .instructions()
+            .iter()
+            .enumerate()
+            .map(|(idx, instruction)| {
+                let idx = idx as i64;
+                match *instruction {
+                    Instruction::Acc(operand) => Op::Acc(operand),
+                    Instruction::Jmp(operand) => Op::Goto(idx + operand as i64),
+                    Instruction::Nop(_) => Op::Goto(idx + 1),
+                    Instruction::Mul(operand) => Op::Mul(operand),
+                    Instruction::Jz(operand) => Op::Jz(idx + operand as i64),
+                    Instruction::Out => Op::Out,
+                    Instruction::Hlt => Op::Hlt,
+                }
+            })
+            .collect();
+
+        CompiledProgram { ops, source: program.instructions().to_vec(), branches_on_accumulator: program.branches_on_accumulator() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    // Same result as run_program_until_terminated on the program this was compiled from.
+    pub fn run_until_terminated(&self, current_state: &ProgramState) -> ProgramState {
+        let mut new_state = current_state.clone();
+        if new_state.terminated() {
+            return new_state;
+        }
+
+        let len = self.ops.len() as i64;
+        let mut seen = vec![false; self.ops.len()];
+        for idx in current_state.visited.indices() {
+            if idx < seen.len() {
+                seen[idx] = true;
+            }
+        }
+
+        let mut last = None;
+        let mut pc = current_state.program_counter as i64;
+        let mut accumulator = current_state.accumulator;
+
+        let termination = loop {
+            if pc == len {
+                break TerminationReason::Finished;
+            }
+            if pc < 0 || pc > len {
+                break TerminationReason::OutOfBounds;
+            }
+
+            let idx = pc as usize;
+            if self.branches_on_accumulator {
+                let state = (pc as i32, accumulator);
+                if new_state.seen_states.contains(&state) {
+                    break TerminationReason::Looped;
+                }
+                if new_state.seen_states.len() >= new_state.step_limit {
+                    break TerminationReason::StepLimit;
+                }
+                new_state.seen_states.insert(state);
+            } else if seen[idx] {
+                break TerminationReason::Looped;
+            }
+            seen[idx] = true;
+            last = Some(idx);
+
+            match self.ops[idx] {
+                Op::Acc(operand) => match accumulator.checked_add(operand) {
+                    Some(sum) => {
+                        accumulator = sum;
+                        pc += 1;
+                    }
+                    None => break TerminationReason::Overflow,
+                },
+                // Too far to hold in the program counter: the interpreter leaves it on the jump.
+                Op::Goto(target) if !fits_program_counter(target) => break TerminationReason::OutOfBounds,
+                Op::Goto(target) => pc = target,
+                Op::Mul(operand) => match accumulator.checked_mul(operand) {
+                    Some(product) => {
+                        accumulator = product;
+                        pc += 1;
+                    }
+                    None => break TerminationReason::Overflow,
+                },
+                Op::Jz(target) if accumulator == 0 && !fits_program_counter(target) => break TerminationReason::OutOfBounds,
+                Op::Jz(target) => pc = if accumulator == 0 { target } else { pc + 1 },
+                Op::Out => {
+                    new_state.output.push(accumulator);
+                    pc += 1;
+                }
+                Op::Hlt => break TerminationReason::Halted,
+            }
+        };
+
+        if let Some(last) = last {
+            new_state.last_instruction = Some((last as i32, self.source[last]));
+        }
+        for idx in (0..seen.len()).filter(|idx| seen[*idx]) {
+            new_state.visited.insert(idx);
+        }
+        new_state.accumulator = accumulator;
+        new_state.program_counter = pc as i32;
+        new_state.termination = Some(termination);
+        new_state
+    }
+}
+
+fn fits_program_counter(target: i64) -> bool {
+    target >= i32::MIN as i64 && target <= i32::MAX as i64
+}
+
+#[cfg(test)]
+mod compiledtests {
+    use super::*;
+    use crate::day8::{load_program, run_program_until_terminated, run_program_until_terminated_on, run_program_x_steps, Engine};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_program(rng: &mut StdRng) -> Program {
+        let len: i32 = rng.gen_range(0..40);
+        let instructions = (0..len)
+            .map(|_| {
+                // Jumps mostly stay inside, with the odd one landing on the end or outside, and
+                // now and then one too far for the program counter to hold.
+                let offset = match rng.gen_range(0..20) {
+                    0 => [i32::MIN, i32::MIN + 1, i32::MAX - 1, i32::MAX][rng.gen_range(0..4)],
+                    _ => rng.gen_range(-len - 2..=len + 2),
+                };
+                // Likewise the odd acc big enough to overflow.
+                let operand = match rng.gen_range(0..20) {
+                    0 => rng.gen_range(i32::MIN..=i32::MAX),
+                    _ => rng.gen_range(-50..=50),
+                };
+                match rng.gen_range(0..10) {
+                    0..=2 => Instruction::Acc(operand),
+                    3..=4 => Instruction::Jmp(offset),
+                    5..=6 => Instruction::Nop(offset),
+                    7 => Instruction::Mul(rng.gen_range(-3..=3)),
+                    8 => Instruction::Jz(offset),
+                    _ => {
+                        if rng.gen_bool(0.7) {
+                            Instruction::Out
+                        } else {
+                            Instruction::Hlt
+                        }
+                    }
+                }
+            })
+            .collect();
+        Program::new(instructions)
+    }
+
+    #[test]
+    fn engines_agree_on_random_programs() {
+        let mut rng = StdRng::seed_from_u64(8);
+
+        for _ in 0..2000 {
+            let program = random_program(&mut rng);
+            // A low step limit keeps the runs that never repeat short.
+            let start = ProgramState::with_step_limit(500);
+            let interpreted = run_program_until_terminated_on(Engine::Interpreted, &start, &program);
+            let compiled = run_program_until_terminated_on(Engine::Compiled, &start, &program);
+
+            assert_eq!(compiled.accumulator, interpreted.accumulator, "{:?}", program.to_lines());
+            assert_eq!(compiled.program_counter, interpreted.program_counter, "{:?}", program.to_lines());
+            assert_eq!(compiled.termination, interpreted.termination, "{:?}", program.to_lines());
+            assert_eq!(compiled, interpreted, "{:?}", program.to_lines());
+        }
+    }
+
+    #[test]
+    fn engines_agree_part_way_through() {
+        let mut rng = StdRng::seed_from_u64(80);
+
+        for _ in 0..500 {
+            let program = random_program(&mut rng);
+            let started = run_program_x_steps(&ProgramState::with_step_limit(500), &program, rng.gen_range(0..5));
+
+            assert_eq!(CompiledProgram::new(&program).run_until_terminated(&started), run_program_until_terminated_on(Engine::Interpreted, &started, &program));
+        }
+    }
+
+    #[test]
+    fn puzzle_input_runs_the_same() {
+        let program = load_program("inputs/day8.txt").unwrap();
+        let compiled = CompiledProgram::new(&program);
+
+        assert_eq!(compiled.len(), program.len());
+        assert_eq!(compiled.run_until_terminated(&ProgramState::new()), run_program_until_terminated(&ProgramState::new(), &program));
+    }
+
+    #[test]
+    fn far_jumps_stop_on_the_jump() {
+        for lines in [vec!["nop +0", "jmp +2147483647"], vec!["nop +0", "jz -2147483648"], vec!["jmp +2147483646"]].iter() {
+            let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+            let program = Program::decode_with(&lines, &crate::day8::assembler::InstructionSet::extended()).unwrap();
+            let interpreted = run_program_until_terminated_on(Engine::Interpreted, &ProgramState::new(), &program);
+
+            assert_eq!(run_program_until_terminated_on(Engine::Compiled, &ProgramState::new(), &program), interpreted);
+            assert_eq!(interpreted.termination, Some(TerminationReason::OutOfBounds));
+        }
+    }
+}
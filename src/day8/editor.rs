@@ -0,0 +1,96 @@
+// Day 8: Handheld Halting - Editor
+// What the program editor shows and does, kept apart from the views so it can be tested.
+// Source goes through the assembler, so comments and labels are fine while experimenting.
+use super::assembler::assemble_text;
+use super::program::TerminationReason;
+use super::{program_autofix, run_program_until_terminated_on, Engine, ProgramState};
+
+// A status line or three for the program as it currently stands.
+pub fn evaluate(source: &str) -> String {
+    let program = match assemble_text(source) {
+        Ok(program) => program,
+        Err(message) => return format!("Doesn't assemble:\n{}", message),
+    };
+
+    let state = run_program_until_terminated_on(Engine::Compiled, &ProgramState::new(), &program);
+    let termination = state.termination.map_or("still running".to_string(), |reason| reason.to_string());
+    let mut lines = vec![format!("{} instructions, {}, acc {}", program.len(), termination, state.accumulator)];
+
+    match (state.termination, state.last_instruction) {
+        (Some(TerminationReason::Looped), Some((pc, instruction))) => {
+            lines.push(format!("Loops at instruction {} ({}) back to {}", pc, instruction, state.program_counter));
+        }
+        (Some(TerminationReason::OutOfBounds), Some((pc, instruction))) => {
+            lines.push(format!("Instruction {} ({}) jumps to {}", pc, instruction, state.program_counter));
+        }
+        _ => {}
+    }
+    if !state.output.is_empty() {
+        lines.push(format!("Output: {:?}", state.output));
+    }
+
+    lines.join("\n")
+}
+
+// The source with program_autofix's swap made, and which instruction it changed. Comments
+// and labels don't survive, as it comes back in the puzzle's encoding.
+pub fn autofix_source(source: &str) -> Result<(String, usize), String> {
+    let program = assemble_text(source)?;
+    match program_autofix(&program) {
+        Some((fixed_program, fixed_at)) => Ok((fixed_program.to_lines().join("\n"), fixed_at as usize)),
+        None => Err("Couldn't find a single jmp/nop swap that fixes it.".to_string()),
+    }
+}
+
+pub fn save_source(path: &str, source: &str) -> Result<String, String> {
+    if path.trim().is_empty() {
+        return Err("Where should it be saved?".to_string());
+    }
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|err| format!("Couldn't save to {}: {}", path, err))?;
+    }
+
+    let mut contents = source.to_string();
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).map_err(|err| format!("Couldn't save to {}: {}", path, err))?;
+    Ok(format!("Saved to {}", path))
+}
+
+#[cfg(test)]
+mod editortests {
+    use super::*;
+
+    fn get_puzzle_example() -> &'static str {
+        "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6"
+    }
+
+    #[test]
+    fn evaluating_shows_the_loop() {
+        assert_eq!(evaluate(get_puzzle_example()), "9 instructions, looped, acc 5\nLoops at instruction 4 (jmp -3) back to 1");
+        assert_eq!(evaluate("acc +2\nout\n# done"), "2 instructions, finished, acc 2\nOutput: [2]");
+        assert_eq!(evaluate("jmp +5"), "1 instructions, jumped out of bounds, acc 0\nInstruction 0 (jmp +5) jumps to 5");
+        assert!(evaluate("acc +1\nhop +2").starts_with("Doesn't assemble:\nline 2: hop isn't an operation"));
+    }
+
+    #[test]
+    fn autofix_rewrites_the_source() {
+        let (fixed, fixed_at) = autofix_source(get_puzzle_example()).unwrap();
+
+        assert_eq!(fixed_at, 7);
+        assert_eq!(fixed.lines().nth(7), Some("nop -4"));
+        assert!(evaluate(&fixed).starts_with("9 instructions, finished, acc 8"));
+        assert!(autofix_source("acc +1").is_err());
+    }
+
+    #[test]
+    fn saving_writes_the_source() {
+        let path = std::env::temp_dir().join("aoc2020_day8_editor").join("edited.txt");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(save_source(path, "acc +1\njmp -1"), Ok(format!("Saved to {}", path)));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "acc +1\njmp -1\n");
+        assert!(save_source("  ", "acc +1").is_err());
+    }
+}
@@ -2,10 +2,19 @@
 // https://adventofcode.com/2020/day/8
 use super::*;
 use std::fs::File;
+use std::collections::HashSet;
 use std::io::{BufRead, BufReader};
-
-use itertools::Itertools;
-
+use cursive::event::{EventResult, EventTrigger};
+use cursive::views::{EditView, LinearLayout, OnEventView, TextArea};
+
+pub mod assembler;
+pub mod cfg;
+pub mod compiled;
+pub mod editor;
+pub mod program;
+pub mod repair;
+pub mod trace;
+use program::{Instruction, Program, TerminationReason, VisitedSet, STEP_LIMIT};
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -21,6 +30,10 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("Repair Options 🔧", 2);
+        view.add_item("Control Flow 🔀", 3);
+        view.add_item("Trace Diff 🔍", 4);
+        view.add_item("Program Editor ✏️", 5);
         view.add_item("Back", 999);
     });
 }
@@ -33,6 +46,18 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            repair_options_prompt(s);
+        }
+        3 => {
+            control_flow(s);
+        }
+        4 => {
+            trace_diff(s);
+        }
+        5 => {
+            program_editor(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -45,13 +70,13 @@ pub fn first_star(s: &mut Cursive) {
     let async_view = AsyncView::new_with_bg_creator(
         s,
         move || {
-            // Load input file and parse it into a vec of ints
-            let bufreader = BufReader::new(File::open("inputs/day8.txt").unwrap());
-            let program: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+            let program = match load_program("inputs/day8.txt") {
+                Ok(program) => program,
+                Err(message) => return Ok(message),
+            };
 
             let recursion_state = run_program_until_terminated(&ProgramState::new(), &program);
-            let last_instruction = recursion_state.last_instruction.unwrap();
-            Ok(format!("Okay, so:\n Acc: {}\n Pc: {}\n\n Li: {}:{} {}\n Tr: {}", recursion_state.accumulator, recursion_state.program_counter, last_instruction.0, last_instruction.1, last_instruction.2, recursion_state.term_reason))
+            Ok(format!("Okay, so:\n{}", describe_state(&recursion_state)))
         },
         TextView::new,
     )
@@ -72,14 +97,18 @@ pub fn second_star(s: &mut Cursive) {
     let async_view = AsyncView::new_with_bg_creator(
         s,
         move || {
-            // Load input file and parse it into a vec of ints
-            let bufreader = BufReader::new(File::open("inputs/day8.txt").unwrap());
-            let program: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
-
-            let (fixed_program, fixed_at) = program_autofix(&program);
-            let fixed_state = run_program_until_terminated(&ProgramState::new(), &fixed_program);
-            let last_instruction = fixed_state.last_instruction.unwrap();
-            Ok(format!("Okay, so fixed program at line {}:\n Acc: {}\n Pc: {}\n\n Li: {}:{} {}\n Tr: {}", fixed_at+1, fixed_state.accumulator, fixed_state.program_counter, last_instruction.0, last_instruction.1, last_instruction.2, fixed_state.term_reason))
+            let program = match load_program("inputs/day8.txt") {
+                Ok(program) => program,
+                Err(message) => return Ok(message),
+            };
+
+            match program_autofix(&program) {
+                Some((fixed_program, fixed_at)) => {
+                    let fixed_state = run_program_until_terminated(&ProgramState::new(), &fixed_program);
+                    Ok(format!("Okay, so fixed program at line {}:\n{}", fixed_at + 1, describe_state(&fixed_state)))
+                }
+                None => Ok("Couldn't find a single jmp/nop swap that fixes it.".to_string()),
+            }
 
         },
         TextView::new,
@@ -96,14 +125,199 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
-#[derive(Clone)]
+pub fn repair_options_prompt(s: &mut Cursive) {
+    let prompt = LinearLayout::vertical()
+        .child(TextView::new("Also try every acc on the run with this operand (leave blank to skip):"))
+        .child(EditView::new().with_name("acc_operand").fixed_width(10));
+
+    s.add_layer(
+        Dialog::around(prompt)
+            .title("Repair Options 🔧")
+            .button("Analyse", |s| {
+                let operand = s.call_on_name("acc_operand", |view: &mut EditView| view.get_content()).unwrap();
+                let acc_operand = match operand.trim() {
+                    "" => None,
+                    text => match text.parse::<i32>() {
+                        Ok(operand) => Some(operand),
+                        Err(_) => {
+                            s.add_layer(Dialog::info(format!("{} isn't an operand", operand)));
+                            return;
+                        }
+                    },
+                };
+                s.pop_layer();
+                repair_options(s, acc_operand);
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn repair_options(s: &mut Cursive, acc_operand: Option<i32>) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let program = match load_program("inputs/day8.txt") {
+                Ok(program) => program,
+                Err(message) => return Ok(message),
+            };
+
+            match repair::analyse(&program, repair::RepairOptions { acc_operand }) {
+                Ok(analysis) => Ok(repair::describe_fixes(&analysis)),
+                Err(message) => Ok(message),
+            }
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(15)
+    .with_width(50);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Repair Options 🔧")
+            .button("Got it!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn control_flow(s: &mut Cursive) {
+    let message = match load_program("inputs/day8.txt") {
+        Ok(program) => {
+            let graph = cfg::ControlFlowGraph::new(&program);
+            match std::fs::create_dir_all("out").and_then(|_| std::fs::write("out/day8_cfg.dot", graph.to_dot())) {
+                Ok(_) => format!("{}\n\nWrote the graph to out/day8_cfg.dot", graph.summary()),
+                Err(err) => format!("{}\n\nCouldn't write the graph: {}", graph.summary(), err),
+            }
+        }
+        Err(message) => message,
+    };
+
+    s.add_layer(
+        Dialog::around(ScrollView::new(TextView::new(message)))
+            .title("Control Flow 🔀")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn trace_diff(s: &mut Cursive) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let program = match load_program("inputs/day8.txt") {
+                Ok(program) => program,
+                Err(message) => return Ok(message),
+            };
+            let fixed_program = match program_autofix(&program) {
+                Some((fixed_program, _)) => fixed_program,
+                None => return Ok("Couldn't find a fix to compare against.".to_string()),
+            };
+
+            let (_, original) = trace::record(&ProgramState::new(), &program);
+            let (_, fixed) = trace::record(&ProgramState::new(), &fixed_program);
+            let written = std::fs::create_dir_all("out")
+                .and_then(|_| std::fs::write("out/day8_original.jsonl", original.to_json_lines()))
+                .and_then(|_| std::fs::write("out/day8_fixed.csv", fixed.to_csv()));
+
+            let saved = match written {
+                Ok(_) => "Traces written to out/day8_original.jsonl and out/day8_fixed.csv".to_string(),
+                Err(err) => format!("Couldn't write the traces: {}", err),
+            };
+            Ok(format!("{}\n\n{}", trace::render_diff(&original, &fixed, 3), saved))
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(20)
+    .with_width(80);
+
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("Trace Diff 🔍")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn editor_source(s: &mut Cursive) -> String {
+    s.call_on_name("day8_editor", |view: &mut TextArea| view.get_content().to_string()).unwrap()
+}
+
+fn refresh_editor_status(s: &mut Cursive) {
+    let status = editor::evaluate(&editor_source(s));
+    s.call_on_name("day8_editor_status", |view: &mut TextView| view.set_content(status));
+}
+
+fn set_editor_source(s: &mut Cursive, source: &str) {
+    s.call_on_name("day8_editor", |view: &mut TextArea| view.set_content(source));
+    refresh_editor_status(s);
+}
+
+pub fn program_editor(s: &mut Cursive) {
+    let original = match std::fs::read_to_string("inputs/day8.txt") {
+        Ok(text) => text.trim_end().to_string(),
+        Err(err) => {
+            s.add_layer(Dialog::info(format!("Couldn't open inputs/day8.txt: {}", err)));
+            return;
+        }
+    };
+
+    // Every edit re-runs the program once the text area has dealt with the key.
+    let text_area = OnEventView::new(TextArea::new().content(original.clone()).with_name("day8_editor")).on_pre_event_inner(EventTrigger::any(), |area, event| {
+        let result = area.on_event(event.clone());
+        if result.is_consumed() {
+            Some(result.and(EventResult::with_cb(refresh_editor_status)))
+        } else {
+            None
+        }
+    });
+
+    let layout = LinearLayout::vertical()
+        .child(ScrollView::new(text_area).fixed_height(20))
+        .child(TextView::new(editor::evaluate(&original)).with_name("day8_editor_status"))
+        .child(TextView::new("Save to:"))
+        .child(EditView::new().content("out/day8_edited.txt").with_name("day8_editor_path"));
+
+    s.add_layer(
+        Dialog::around(layout.fixed_width(60))
+            .title("Program Editor ✏️")
+            .button("Autofix", |s| match editor::autofix_source(&editor_source(s)) {
+                Ok((fixed, fixed_at)) => {
+                    set_editor_source(s, &fixed);
+                    s.add_layer(Dialog::info(format!("Swapped line {}", fixed_at + 1)));
+                }
+                Err(message) => s.add_layer(Dialog::info(message)),
+            })
+            .button("Revert", move |s| set_editor_source(s, &original))
+            .button("Save", |s| {
+                let path = s.call_on_name("day8_editor_path", |view: &mut EditView| view.get_content()).unwrap();
+                let message = match editor::save_source(&path, &editor_source(s)) {
+                    Ok(message) | Err(message) => message,
+                };
+                s.add_layer(Dialog::info(message));
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ProgramState {
-    accumulator: i32,
-    program_counter: i32,
-    last_instruction: Option<(i32, String, i32)>,
-    visited_indices: Vec<i32>,
-    terminated: bool,
-    term_reason: i32
+    pub accumulator: i32,
+    pub program_counter: i32,
+    pub last_instruction: Option<(i32, Instruction)>,
+    pub visited: VisitedSet,
+    // Every (program counter, accumulator) seen so far, kept only for programs with jz.
+    pub seen_states: HashSet<(i32, i32)>,
+    // How many states a program with jz can go through before it's given up on.
+    pub step_limit: usize,
+    pub termination: Option<TerminationReason>,
+    // Everything out has written, in order.
+    pub output: Vec<i32>,
 }
 
 impl ProgramState {
@@ -112,132 +326,202 @@ impl ProgramState {
             accumulator: 0,
             program_counter: 0,
             last_instruction: None,
-            visited_indices: Vec::new(),
-            terminated: false,
-            term_reason: 0,
+            visited: VisitedSet::new(0),
+            seen_states: HashSet::new(),
+            step_limit: STEP_LIMIT,
+            termination: None,
+            output: Vec::new(),
         }
     }
-}
-
-pub fn get_instruction(line: &String) -> (String, i32) {
-    let split_line = line.split(' ').map(|s| s.to_string()).collect::<Vec<String>>();
-    (split_line[0].to_string(), split_line[1].parse().unwrap())
-}
 
-pub fn step_program_forward(current_state: &ProgramState, program: &Vec<String>) -> ProgramState {
-    if current_state.terminated {
-        return current_state.clone();
+    pub fn with_step_limit(step_limit: usize) -> ProgramState {
+        ProgramState { step_limit, ..ProgramState::new() }
     }
 
-    let mut new_state = current_state.clone();
-
-    if program.len() == current_state.program_counter as usize {
-        new_state.terminated = true;
-        new_state.term_reason = 0;
-        return new_state;
+    pub fn terminated(&self) -> bool {
+        self.termination.is_some()
     }
 
-    let current_line = &program[current_state.program_counter as usize];
-    
-    new_state.visited_indices.push(current_state.program_counter);
-
-    let (operator, operand) = get_instruction(&current_line);
+    // Runs one instruction in place, and says whether one ran. Coming back to an earlier
+    // state, or to the end of the program, only marks the state as terminated.
+    pub fn step(&mut self, program: &Program) -> bool {
+        if self.terminated() {
+            return false;
+        }
 
-    match operator.as_str() {
-        "acc" => {
-            new_state.accumulator += operand;
-            new_state.program_counter += 1;
-        },
-        "jmp" => {
-            new_state.program_counter += operand;
+        let instruction = match program.get(self.program_counter) {
+            Some(instruction) => instruction,
+            None if self.program_counter as usize == program.len() => {
+                self.termination = Some(TerminationReason::Finished);
+                return false;
+            }
+            None => {
+                self.termination = Some(TerminationReason::OutOfBounds);
+                return false;
+            }
+        };
+
+        let pc = self.program_counter as usize;
+        if program.branches_on_accumulator() {
+            // jz can send the same instruction different ways, so it's only a loop once the
+            // accumulator repeats along with it.
+            if self.seen_states.contains(&(self.program_counter, self.accumulator)) {
+                self.termination = Some(TerminationReason::Looped);
+                return false;
+            }
+            if self.seen_states.len() >= self.step_limit {
+                self.termination = Some(TerminationReason::StepLimit);
+                return false;
+            }
+            self.seen_states.insert((self.program_counter, self.accumulator));
+        } else if self.visited.contains(pc) {
+            self.termination = Some(TerminationReason::Looped);
+            return false;
         }
-        "nop" => {
-            new_state.program_counter += 1;
+        self.visited.insert(pc);
+
+        match instruction {
+            Instruction::Acc(operand) => match self.accumulator.checked_add(operand) {
+                Some(accumulator) => {
+                    self.accumulator = accumulator;
+                    self.program_counter += 1;
+                }
+                None => self.termination = Some(TerminationReason::Overflow),
+            },
+            Instruction::Jmp(operand) => {
+                self.jump_by(operand);
+            }
+            Instruction::Nop(_) => {
+                self.program_counter += 1;
+            }
+            Instruction::Mul(operand) => match self.accumulator.checked_mul(operand) {
+                Some(accumulator) => {
+                    self.accumulator = accumulator;
+                    self.program_counter += 1;
+                }
+                None => self.termination = Some(TerminationReason::Overflow),
+            },
+            Instruction::Jz(operand) => {
+                self.jump_by(if self.accumulator == 0 { operand } else { 1 });
+            }
+            Instruction::Out => {
+                self.output.push(self.accumulator);
+                self.program_counter += 1;
+            }
+            Instruction::Hlt => {
+                self.termination = Some(TerminationReason::Halted);
+            }
         }
-        _ => {}
-    }
-
-    new_state.last_instruction = Some((current_state.program_counter, operator, operand));
 
-    // Check that we didn't visit the same instruction twice.
-    if new_state.visited_indices.len() != new_state.visited_indices.clone().into_iter().unique().collect::<Vec<i32>>().len() {
-        new_state = current_state.clone();
-        new_state.terminated = true;
-        new_state.term_reason = 1;
+        self.last_instruction = Some((pc as i32, instruction));
+        true
     }
 
-    
-
-    new_state
+    // A jump too far to even hold in the program counter leaves it on the jump and ends the run.
+    fn jump_by(&mut self, offset: i32) {
+        match self.program_counter.checked_add(offset) {
+            Some(target) => self.program_counter = target,
+            None => self.termination = Some(TerminationReason::OutOfBounds),
+        }
+    }
 }
 
-pub fn run_program_x_steps(current_state: &ProgramState, program: &Vec<String>, steps: i32) -> ProgramState {
-    let mut new_state = current_state.clone();
-
-    for _ in 0 .. steps {
-        new_state = step_program_forward(&new_state, program);
+impl Default for ProgramState {
+    fn default() -> ProgramState {
+        ProgramState::new()
     }
+}
 
+pub fn get_instruction(line: &str) -> Result<Instruction, String> {
+    line.parse()
+}
 
+pub fn step_program_forward(current_state: &ProgramState, program: &Program) -> ProgramState {
+    let mut new_state = current_state.clone();
+    new_state.step(program);
     new_state
 }
 
-pub fn run_program_until_terminated(current_state: &ProgramState, program: &Vec<String>) -> ProgramState {
+pub fn run_program_x_steps(current_state: &ProgramState, program: &Program, steps: i32) -> ProgramState {
     let mut new_state = current_state.clone();
 
-    while !new_state.terminated {
-        new_state = step_program_forward(&new_state, program);
+    for _ in 0 .. steps {
+        new_state.step(program);
     }
 
     new_state
 }
 
-pub fn program_autofix(broken_program: &Vec<String>) -> (Vec<String>, i32) {
-    let mut new_program = broken_program.clone();
-    let mut line_fix = 0;
-    let mut program_state = run_program_until_terminated(&ProgramState::new(),&new_program);
+// Which way to run a program. Both give the same ProgramState; the compiled engine is
+// quicker on long runs but spends a pass over the program getting ready.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    #[default]
+    Interpreted,
+    Compiled,
+}
 
-    while program_state.term_reason == 1 {
+pub fn run_program_until_terminated(current_state: &ProgramState, program: &Program) -> ProgramState {
+    run_program_until_terminated_on(Engine::default(), current_state, program)
+}
 
-        // Get visited indices from last run.
-        let visited_indices = program_state.visited_indices.clone();
+pub fn run_program_until_terminated_on(engine: Engine, current_state: &ProgramState, program: &Program) -> ProgramState {
+    match engine {
+        Engine::Interpreted => {
+            let mut new_state = current_state.clone();
 
-        for idx in visited_indices {
-            // Manipulate the program. 
-            let mut temp_new_program = new_program.clone();
-            if get_instruction(&temp_new_program[idx as usize]).0.as_str() == "jmp" {
-                temp_new_program[idx as usize] = "nop +0".to_string();
-            }
-            else if get_instruction(&temp_new_program[idx as usize]).0.as_str() == "nop" {
-                temp_new_program[idx as usize] = format!("jmp {}", get_instruction(&temp_new_program[idx as usize]).1); 
+            while !new_state.terminated() {
+                new_state.step(program);
             }
 
-            let test_fix_program = run_program_until_terminated(&ProgramState::new(),&temp_new_program);
-            if test_fix_program.term_reason == 0 {
-                line_fix = idx;
-                new_program = temp_new_program.clone();
-                break;
-            }
+            new_state
         }
+        Engine::Compiled => compiled::CompiledProgram::new(program).run_until_terminated(current_state),
+    }
+}
 
-        program_state = run_program_until_terminated(&ProgramState::new(),&new_program);
+// The first single jmp/nop swap that makes the program finish, and where it was made.
+// Programs the repair analysis can't handle get every swap tried in turn instead.
+pub fn program_autofix(broken_program: &Program) -> Option<(Program, i32)> {
+    match repair::analyse(broken_program, repair::RepairOptions::default()) {
+        Ok(analysis) => analysis.fixes.first().map(|fix| (repair::apply_fix(broken_program, fix), fix.index as i32)),
+        Err(_) => (0..broken_program.len())
+            .filter(|idx| broken_program.instructions()[*idx].flipped() != broken_program.instructions()[*idx])
+            .map(|idx| (broken_program.with_flip(idx), idx as i32))
+            .find(|(fixed_program, _)| run_program_until_terminated_on(Engine::Compiled, &ProgramState::new(), fixed_program).termination == Some(TerminationReason::Finished)),
     }
+}
 
-    (new_program, line_fix)
+pub fn describe_state(state: &ProgramState) -> String {
+    let last_instruction = match &state.last_instruction {
+        Some((pc, instruction)) => format!("{}:{}", pc, instruction),
+        None => "none".to_string(),
+    };
+    let termination = match state.termination {
+        Some(reason) => reason.to_string(),
+        None => "still running".to_string(),
+    };
+
+    format!(" Acc: {}\n Pc: {}\n\n Li: {}\n Tr: {}", state.accumulator, state.program_counter, last_instruction, termination)
 }
 
+pub fn load_program(path: &str) -> Result<Program, String> {
+    let bufreader = BufReader::new(File::open(path).map_err(|err| format!("Couldn't open {}: {}", path, err))?);
+    let lines: Vec<String> = bufreader.lines().map(|line| line.unwrap()).collect();
+    Program::decode(&lines)
+}
 
 #[cfg(test)]
 mod day8tests {
     use super::*;
-    
+
+    fn decode(lines: &[&str]) -> Program {
+        Program::decode(&lines.iter().map(|line| line.to_string()).collect::<Vec<String>>()).unwrap()
+    }
+
     #[test]
     pub fn test_operators() {
-        let test_program = vec![
-            "acc +1".to_string(),
-            "nop +0".to_string(),
-            "jmp -2".to_string(),
-        ];
+        let test_program = decode(&["acc +1", "nop +0", "jmp -2"]);
 
         let initial_program_state = ProgramState::new();
 
@@ -252,11 +536,7 @@ mod day8tests {
 
     #[test]
     pub fn test_run_x_steps() {
-        let test_program = vec![
-            "acc +1".to_string(),
-            "nop +0".to_string(),
-            "jmp -2".to_string(),
-        ];
+        let test_program = decode(&["acc +1", "nop +0", "jmp -2"]);
 
         let initial_program_state = ProgramState::new();
 
@@ -264,16 +544,12 @@ mod day8tests {
 
         assert_eq!(run_state.accumulator, 1);
         assert_eq!(run_state.program_counter, 2);
-        assert_eq!(run_state.terminated, false);
+        assert!(!run_state.terminated());
     }
 
     #[test]
     pub fn test_until_normal_termination() {
-        let test_program = vec![
-            "acc +1".to_string(),
-            "nop +0".to_string(),
-            "nop +0".to_string(),
-        ];
+        let test_program = decode(&["acc +1", "nop +0", "nop +0"]);
 
         let initial_program_state = ProgramState::new();
 
@@ -281,16 +557,12 @@ mod day8tests {
 
         assert_eq!(run_state.accumulator, 1);
         assert_eq!(run_state.program_counter, 3);
-        assert_eq!(run_state.term_reason, 0);
+        assert_eq!(run_state.termination, Some(TerminationReason::Finished));
     }
 
     #[test]
     pub fn test_until_repeated_instruction() {
-        let test_program = vec![
-            "acc +1".to_string(),
-            "nop +0".to_string(),
-            "jmp -2".to_string(),
-        ];
+        let test_program = decode(&["acc +1", "nop +0", "jmp -2"]);
 
         let initial_program_state = ProgramState::new();
 
@@ -298,20 +570,95 @@ mod day8tests {
 
         assert_eq!(run_state.accumulator, 1);
         assert_eq!(run_state.program_counter, 0);
-        assert_eq!(run_state.term_reason, 1);
+        assert_eq!(run_state.termination, Some(TerminationReason::Looped));
+        assert_eq!(run_state.last_instruction, Some((2, Instruction::Jmp(-2))));
     }
 
     #[test]
     pub fn test_program_autofix() {
-        let test_program = vec![
-            "acc +1".to_string(),
-            "nop +0".to_string(),
-            "jmp -2".to_string(),
-        ];
+        let test_program = decode(&["acc +1", "nop +0", "jmp -2"]);
 
-        let fixed_program = program_autofix(&test_program);
+        let fixed_program = program_autofix(&test_program).unwrap();
 
         assert_eq!(fixed_program.0.len(), test_program.len());
-        assert_eq!(fixed_program.0[2],"nop +0".to_string());
+        assert_eq!(fixed_program.0.get(2), Some(Instruction::Nop(-2)));
+        assert_eq!(fixed_program.1, 2);
+
+        // Halting and overflowing don't count as finishing.
+        assert_eq!(program_autofix(&assembler::assemble_text("nop +0\njmp -1\nhlt").unwrap()), None);
+        assert_eq!(program_autofix(&decode(&["acc +2147483647", "acc +1", "jmp -2"])), None);
+
+        // The repair analysis turns down programs with jz, so these get fixed by trying every swap.
+        let conditional = assembler::assemble_text("acc +1\ntop: jz done\njmp top\ndone: nop +0").unwrap();
+        let (fixed_program, fixed_at) = program_autofix(&conditional).unwrap();
+
+        assert_eq!(fixed_at, 2);
+        assert_eq!(run_program_until_terminated(&ProgramState::new(), &fixed_program).termination, Some(TerminationReason::Finished));
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_out_of_bounds_jump() {
+        let test_program = decode(&["nop +0", "jmp +5"]);
+
+        let run_state = run_program_until_terminated(&ProgramState::new(), &test_program);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::OutOfBounds));
+        assert_eq!(run_state.program_counter, 6);
+
+        let test_program = decode(&["nop +0", "jmp +2147483647"]);
+        let run_state = run_program_until_terminated(&ProgramState::new(), &test_program);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::OutOfBounds));
+        assert_eq!(run_state.program_counter, 1);
+        assert_eq!(run_state.last_instruction, Some((1, Instruction::Jmp(i32::MAX))));
+    }
+
+    #[test]
+    pub fn test_accumulator_overflow() {
+        let test_program = decode(&["acc +2147483647", "acc +1", "nop +0"]);
+
+        let run_state = run_program_until_terminated(&ProgramState::new(), &test_program);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::Overflow));
+        assert_eq!(run_state.accumulator, i32::MAX);
+        assert_eq!(run_state.program_counter, 1);
+        assert_eq!(run_state.last_instruction, Some((1, Instruction::Acc(1))));
+    }
+
+    #[test]
+    pub fn test_jz_loops() {
+        // Counts down from three, going round the same three instructions each time.
+        let countdown = assembler::assemble_text("acc +3\ntop: acc -1\njz done\njmp top\ndone: hlt").unwrap();
+        let run_state = run_program_until_terminated(&ProgramState::new(), &countdown);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::Halted));
+        assert_eq!(run_state.accumulator, 0);
+
+        // Back at the same instruction with the same accumulator is still a loop.
+        let stuck = assembler::assemble_text("nop +0\njz +0").unwrap();
+        let run_state = run_program_until_terminated(&ProgramState::new(), &stuck);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::Looped));
+        assert_eq!(run_state.program_counter, 1);
+
+        // Never repeats, as the accumulator keeps growing, so the step limit stops it.
+        let endless = assembler::assemble_text("top: acc +1\njz top\njmp top").unwrap();
+        let run_state = run_program_until_terminated(&ProgramState::new(), &endless);
+
+        assert_eq!(run_state.termination, Some(TerminationReason::StepLimit));
+        assert_eq!(run_state.seen_states.len(), STEP_LIMIT);
+
+        let run_state = run_program_until_terminated(&ProgramState::with_step_limit(10), &endless);
+        assert_eq!(run_state.termination, Some(TerminationReason::StepLimit));
+        assert_eq!(run_state.seen_states.len(), 10);
+    }
+
+    #[test]
+    pub fn test_puzzle_input() {
+        let program = load_program("inputs/day8.txt").unwrap();
+        let (fixed_program, _) = program_autofix(&program).unwrap();
+
+        assert_eq!(run_program_until_terminated(&ProgramState::new(), &program).termination, Some(TerminationReason::Looped));
+        assert_eq!(run_program_until_terminated(&ProgramState::new(), &fixed_program).termination, Some(TerminationReason::Finished));
+    }
+}
@@ -0,0 +1,267 @@
+// Day 8: Handheld Halting - Programs
+// Source lines are decoded into instructions once, up front, rather than on every step.
+use super::assembler::InstructionSet;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Acc(i32),
+    Jmp(i32),
+    Nop(i32),
+    // Extensions for hand-written programs; the puzzle only uses the three above.
+    Mul(i32),
+    Jz(i32),
+    Out,
+    Hlt,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Acc(_) => "acc",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Nop(_) => "nop",
+            Instruction::Mul(_) => "mul",
+            Instruction::Jz(_) => "jz",
+            Instruction::Out => "out",
+            Instruction::Hlt => "hlt",
+        }
+    }
+
+    pub fn operand(&self) -> Option<i32> {
+        match self {
+            Instruction::Acc(operand) | Instruction::Jmp(operand) | Instruction::Nop(operand) | Instruction::Mul(operand) | Instruction::Jz(operand) => Some(*operand),
+            Instruction::Out | Instruction::Hlt => None,
+        }
+    }
+
+    // jmp and nop swapped, as the puzzle's corruption does. Anything else is unchanged.
+    pub fn flipped(&self) -> Instruction {
+        match self {
+            Instruction::Jmp(operand) => Instruction::Nop(*operand),
+            Instruction::Nop(operand) => Instruction::Jmp(*operand),
+            other => *other,
+        }
+    }
+}
+
+// Only what the puzzle's handheld understands. Decode with InstructionSet::extended to
+// allow the rest.
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Instruction, String> {
+        InstructionSet::shared_puzzle().decode(line)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.operand() {
+            Some(operand) => write!(f, "{} {:+}", self.mnemonic(), operand),
+            None => write!(f, "{}", self.mnemonic()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    // Whether any jz is in there, which makes control flow depend on the accumulator.
+    branches_on_accumulator: bool,
+}
+
+fn any_branches_on_accumulator(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instruction| matches!(instruction, Instruction::Jz(_)))
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Program {
+        let branches_on_accumulator = any_branches_on_accumulator(&instructions);
+        Program { instructions, branches_on_accumulator }
+    }
+
+    // Puzzle input: acc, jmp and nop only.
+    pub fn decode(lines: &[String]) -> Result<Program, String> {
+        Program::decode_with(lines, InstructionSet::shared_puzzle())
+    }
+
+    pub fn decode_with(lines: &[String], isa: &InstructionSet) -> Result<Program, String> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| isa.decode(line).map_err(|err| format!("Line {}: {}", idx + 1, err)))
+            .collect::<Result<Vec<Instruction>, String>>()
+            .map(Program::new)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn get(&self, pc: i32) -> Option<Instruction> {
+        if pc < 0 {
+            return None;
+        }
+        self.instructions.get(pc as usize).copied()
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn branches_on_accumulator(&self) -> bool {
+        self.branches_on_accumulator
+    }
+
+    pub fn set(&mut self, idx: usize, instruction: Instruction) {
+        self.instructions[idx] = instruction;
+        self.branches_on_accumulator = any_branches_on_accumulator(&self.instructions);
+    }
+
+    // A copy with the instruction at idx flipped.
+    pub fn with_flip(&self, idx: usize) -> Program {
+        let mut program = self.clone();
+        program.instructions[idx] = program.instructions[idx].flipped();
+        program
+    }
+
+    pub fn to_lines(&self) -> Vec<String> {
+        self.instructions.iter().map(|instruction| instruction.to_string()).collect()
+    }
+}
+
+// One bit per instruction, for loop detection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VisitedSet {
+    words: Vec<u64>,
+}
+
+impl VisitedSet {
+    pub fn new(len: usize) -> VisitedSet {
+        VisitedSet { words: vec![0; len.div_ceil(64)] }
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.words.get(idx / 64).is_some_and(|word| word & (1 << (idx % 64)) != 0)
+    }
+
+    pub fn insert(&mut self, idx: usize) {
+        if idx / 64 >= self.words.len() {
+            self.words.resize(idx / 64 + 1, 0);
+        }
+        self.words[idx / 64] |= 1 << (idx % 64);
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    pub fn indices(&self) -> Vec<usize> {
+        (0..self.words.len() * 64).filter(|idx| self.contains(*idx)).collect()
+    }
+}
+
+// How long a program with jz gets by default. Without jz every instruction runs at most once.
+pub const STEP_LIMIT: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    // The program counter landed just past the last instruction.
+    Finished,
+    // The program came back to a state it had already been in: the same instruction, or
+    // with jz around, the same instruction and accumulator.
+    Looped,
+    // The program counter landed somewhere else outside the program.
+    OutOfBounds,
+    // A hlt instruction ran.
+    Halted,
+    // An acc or mul would have taken the accumulator past what an i32 holds.
+    Overflow,
+    // A program with jz used up its step limit without finishing or repeating itself.
+    StepLimit,
+}
+
+impl fmt::Display for TerminationReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerminationReason::Finished => write!(f, "finished"),
+            TerminationReason::Looped => write!(f, "looped"),
+            TerminationReason::OutOfBounds => write!(f, "jumped out of bounds"),
+            TerminationReason::Halted => write!(f, "halted"),
+            TerminationReason::Overflow => write!(f, "overflowed the accumulator"),
+            TerminationReason::StepLimit => write!(f, "gave up after too many steps"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod programtests {
+    use super::*;
+
+    #[test]
+    fn instructions_decode() {
+        assert_eq!("acc +1".parse(), Ok(Instruction::Acc(1)));
+        assert_eq!("jmp -20".parse(), Ok(Instruction::Jmp(-20)));
+        assert_eq!("nop +0".parse(), Ok(Instruction::Nop(0)));
+        assert!("hop +2".parse::<Instruction>().is_err());
+        assert!("mul +2".parse::<Instruction>().is_err());
+        assert!("hlt".parse::<Instruction>().is_err());
+        assert!("acc".parse::<Instruction>().is_err());
+        assert!("acc one".parse::<Instruction>().is_err());
+        assert_eq!(Instruction::Jmp(-4).to_string(), "jmp -4");
+        assert_eq!(Instruction::Acc(0).to_string(), "acc +0");
+        assert_eq!(Instruction::Out.to_string(), "out");
+    }
+
+    #[test]
+    fn programs_decode_and_flip() {
+        let lines: Vec<String> = ["nop +0", "acc +1", "jmp -2"].iter().map(|line| line.to_string()).collect();
+        let program = Program::decode(&lines).unwrap();
+
+        assert_eq!(program.len(), 3);
+        assert_eq!(program.get(2), Some(Instruction::Jmp(-2)));
+        assert_eq!(program.get(-1), None);
+        assert_eq!(program.with_flip(2).get(2), Some(Instruction::Nop(-2)));
+        assert_eq!(program.with_flip(1), program);
+        assert_eq!(program.to_lines(), lines);
+
+        let broken: Vec<String> = ["nop +0", "hop +1"].iter().map(|line| line.to_string()).collect();
+        assert_eq!(Program::decode(&broken), Err("Line 2: hop isn't an operation".to_string()));
+    }
+
+    #[test]
+    fn extensions_are_opt_in() {
+        let lines: Vec<String> = ["acc +2", "mul +2", "jz -1", "out", "hlt"].iter().map(|line| line.to_string()).collect();
+
+        assert_eq!(Program::decode(&lines), Err("Line 2: mul isn't an operation".to_string()));
+
+        let program = Program::decode_with(&lines, &InstructionSet::extended()).unwrap();
+        assert_eq!(program.instructions(), &[Instruction::Acc(2), Instruction::Mul(2), Instruction::Jz(-1), Instruction::Out, Instruction::Hlt]);
+        assert!(program.branches_on_accumulator());
+        assert!(!Program::decode(&lines[..1]).unwrap().branches_on_accumulator());
+        assert!(InstructionSet::extended().decode("out +2").is_err());
+    }
+
+    #[test]
+    fn visited_set_works() {
+        let mut visited = VisitedSet::new(100);
+
+        visited.insert(3);
+        visited.insert(99);
+        visited.insert(200);
+        assert!(visited.contains(99));
+        assert!(!visited.contains(4));
+        assert_eq!(visited.len(), 3);
+        assert_eq!(visited.indices(), vec![3, 99, 200]);
+    }
+}
@@ -0,0 +1,343 @@
+// Day 8: Handheld Halting - Repair Analysis
+// Rather than re-running the program once per candidate, work backwards from the end:
+// an instruction "reaches the end" if following the program from it finishes. Only
+// instructions on the original run can be the corrupted one, and flipping one of those
+// fixes the program exactly when its new target reaches the end without the accumulator
+// overflowing on the way. A hlt stops the run but doesn't finish it. One pass each way, so
+// the whole thing is linear in the length of the program.
+use super::program::{Instruction, Program};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fix {
+    pub index: usize,
+    pub original: Instruction,
+    pub replacement: Instruction,
+    // What the accumulator ends up as once the fixed program finishes.
+    pub accumulator: i32,
+}
+
+// An acc on the original run given a different operand. That can't change where the run
+// goes, only what it adds up to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccEdit {
+    pub index: usize,
+    pub original: Instruction,
+    pub replacement: Instruction,
+    // What the accumulator is when the run stops, finishing or looping just as before.
+    pub accumulator: i32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RepairOptions {
+    // Also work out the accumulator with each acc on the original run set to this operand.
+    pub acc_operand: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepairAnalysis {
+    // For each instruction: if following the program from it runs off the end, the amount
+    // added to the accumulator on the way there. Whether the accumulator survives the trip
+    // depends on what it starts at, which fixes and finishes_with take into account.
+    pub reaches_end: Vec<Option<i64>>,
+    // What the accumulator finishes at if the program already finishes as it is.
+    pub finishes_with: Option<i32>,
+    // The original run, in the order the instructions executed.
+    pub original_path: Vec<usize>,
+    pub fixes: Vec<Fix>,
+    pub acc_edits: Vec<AccEdit>,
+}
+
+// Following the program from an instruction to the end: the total it adds to the
+// accumulator, and the lowest and highest it takes it relative to where it started.
+#[derive(Clone, Copy, Debug)]
+struct Reach {
+    total: i64,
+    low: i64,
+    high: i64,
+}
+
+impl Reach {
+    const END: Reach = Reach { total: 0, low: 0, high: 0 };
+
+    // The same trip with one more instruction, adding delta, in front.
+    fn after(self, delta: i32) -> Option<Reach> {
+        let delta = delta as i64;
+        Some(Reach { total: delta.checked_add(self.total)?, low: delta.checked_add(self.low)?.min(0), high: delta.checked_add(self.high)?.max(0) })
+    }
+
+    // Where the accumulator ends up starting from accumulator, unless it overflows on the way,
+    // which stops the run before it gets there.
+    fn finish_from(self, accumulator: i32) -> Option<i32> {
+        let accumulator = accumulator as i64;
+        if accumulator + self.low < i32::MIN as i64 || accumulator + self.high > i32::MAX as i64 {
+            return None;
+        }
+        Some((accumulator + self.total) as i32)
+    }
+}
+
+// Where control goes after each instruction, if it stays in the program or lands just past it.
+// None means the run stops there some other way. A hlt stops it without finishing.
+fn successor(program: &Program, idx: usize) -> Option<usize> {
+    let target = match program.instructions()[idx] {
+        Instruction::Jmp(operand) => idx as i64 + operand as i64,
+        Instruction::Hlt => return None,
+        _ => idx as i64 + 1,
+    };
+
+    if target >= 0 && target <= program.len() as i64 {
+        Some(target as usize)
+    } else {
+        None
+    }
+}
+
+fn acc_delta(instruction: Instruction) -> i32 {
+    match instruction {
+        Instruction::Acc(operand) => operand,
+        _ => 0,
+    }
+}
+
+pub fn analyse(program: &Program, options: RepairOptions) -> Result<RepairAnalysis, String> {
+    if let Some(instruction) = program.instructions().iter().find(|instruction| matches!(instruction, Instruction::Mul(_) | Instruction::Jz(_))) {
+        return Err(format!("Can't analyse programs using {}; the result depends on the accumulator", instruction.mnemonic()));
+    }
+
+    let len = program.len();
+
+    // Reverse edges, with len standing for "finished".
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+    for idx in 0..len {
+        if let Some(target) = successor(program, idx) {
+            predecessors[target].push(idx);
+        }
+    }
+
+    let mut reaches: Vec<Option<Reach>> = vec![None; len + 1];
+    reaches[len] = Some(Reach::END);
+    let mut stack = vec![len];
+    while let Some(target) = stack.pop() {
+        let reach = reaches[target].unwrap();
+        for &idx in &predecessors[target] {
+            // A trip adding up to more than an i64 holds can't be survived from any start.
+            if let Some(before) = reach.after(acc_delta(program.instructions()[idx])) {
+                reaches[idx] = Some(before);
+                stack.push(idx);
+            }
+        }
+    }
+
+    // The original run, stopping at the first repeat, when it leaves the program, or when the
+    // accumulator overflows.
+    let mut original_path = Vec::new();
+    let mut seen = vec![false; len];
+    let mut accumulator_before = Vec::new();
+    let mut accumulator = 0i32;
+    let mut overflowed = false;
+    let mut pc = 0usize;
+    while pc < len && !seen[pc] {
+        seen[pc] = true;
+        original_path.push(pc);
+        accumulator_before.push(accumulator);
+        match accumulator.checked_add(acc_delta(program.instructions()[pc])) {
+            Some(sum) => accumulator = sum,
+            None => {
+                overflowed = true;
+                break;
+            }
+        }
+        match successor(program, pc) {
+            Some(next) => pc = next,
+            None => break,
+        }
+    }
+
+    let finishes_with = reaches[0].and_then(|reach| reach.finish_from(0));
+    let mut fixes = Vec::new();
+    if finishes_with.is_none() {
+        for (step, &idx) in original_path.iter().enumerate() {
+            let original = program.instructions()[idx];
+            let replacement = match original {
+                Instruction::Jmp(_) | Instruction::Nop(_) => original.flipped(),
+                _ => continue,
+            };
+
+            let target = match replacement {
+                Instruction::Jmp(operand) => idx as i64 + operand as i64,
+                _ => idx as i64 + 1,
+            };
+            if target < 0 || target > len as i64 {
+                continue;
+            }
+
+            if let Some(accumulator) = reaches[target as usize].and_then(|reach| reach.finish_from(accumulator_before[step])) {
+                fixes.push(Fix { index: idx, original, replacement, accumulator });
+            }
+        }
+    }
+
+    // Control flow doesn't depend on the accumulator here, so an edited run takes the same
+    // path, as long as neither it nor the original overflows and stops early.
+    let mut acc_edits = Vec::new();
+    if let (Some(operand), false) = (options.acc_operand, overflowed) {
+        for &idx in &original_path {
+            if let Instruction::Acc(before) = program.instructions()[idx] {
+                let replacement = Instruction::Acc(operand);
+                let edited = original_path.iter().try_fold(0i32, |sum, step| sum.checked_add(if *step == idx { operand } else { acc_delta(program.instructions()[*step]) }));
+                if let Some(accumulator) = edited {
+                    acc_edits.push(AccEdit { index: idx, original: Instruction::Acc(before), replacement, accumulator });
+                }
+            }
+        }
+    }
+
+    let reaches_end = reaches[..len].iter().map(|reach| reach.map(|reach| reach.total)).collect();
+    Ok(RepairAnalysis { reaches_end, finishes_with, original_path, fixes, acc_edits })
+}
+
+pub fn apply_fix(program: &Program, fix: &Fix) -> Program {
+    let mut fixed = program.clone();
+    fixed.set(fix.index, fix.replacement);
+    fixed
+}
+
+pub fn describe_fixes(analysis: &RepairAnalysis) -> String {
+    let mut lines = if analysis.finishes_with.is_some() {
+        vec!["The program already terminates.".to_string()]
+    } else if analysis.fixes.is_empty() {
+        vec!["No single change makes it terminate.".to_string()]
+    } else {
+        analysis
+            .fixes
+            .iter()
+            .map(|fix| format!("Line {}: {} -> {}, acc ends at {}", fix.index + 1, fix.original, fix.replacement, fix.accumulator))
+            .collect()
+    };
+
+    if !analysis.acc_edits.is_empty() {
+        lines.push(String::new());
+        lines.push("Editing an acc can't change where the run goes. With just that edit, it stops with acc at:".to_string());
+        lines.extend(analysis.acc_edits.iter().map(|edit| format!("Line {}: {} -> {}, acc {}", edit.index + 1, edit.original, edit.replacement, edit.accumulator)));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod repairtests {
+    use super::*;
+    use crate::day8::assembler::InstructionSet;
+    use crate::day8::program::TerminationReason;
+    use crate::day8::{load_program, run_program_until_terminated, ProgramState};
+
+    fn decode(lines: &[&str]) -> Program {
+        Program::decode_with(&lines.iter().map(|line| line.to_string()).collect::<Vec<String>>(), &InstructionSet::extended()).unwrap()
+    }
+
+    fn get_puzzle_example() -> Program {
+        decode(&["nop +0", "acc +1", "jmp +4", "acc +3", "jmp -3", "acc -99", "acc +1", "jmp -4", "acc +6"])
+    }
+
+    #[test]
+    fn puzzle_example_has_one_fix() {
+        let analysis = analyse(&get_puzzle_example(), RepairOptions::default()).unwrap();
+
+        assert_eq!(analysis.original_path, vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(analysis.fixes, vec![Fix { index: 7, original: Instruction::Jmp(-4), replacement: Instruction::Nop(-4), accumulator: 8 }]);
+        assert_eq!(analysis.reaches_end[8], Some(6));
+        assert_eq!(analysis.reaches_end[0], None);
+    }
+
+    #[test]
+    fn every_fix_is_found_and_checks_out() {
+        // Both the nop at the start and the jmp at the end will do.
+        let program = decode(&["nop +3", "acc +1", "jmp -1", "acc +5"]);
+        let analysis = analyse(&program, RepairOptions::default()).unwrap();
+
+        assert_eq!(analysis.fixes.iter().map(|fix| fix.index).collect::<Vec<usize>>(), vec![0, 2]);
+        for fix in &analysis.fixes {
+            let state = run_program_until_terminated(&ProgramState::new(), &apply_fix(&program, fix));
+            assert_eq!(state.termination, Some(TerminationReason::Finished));
+            assert_eq!(state.accumulator, fix.accumulator);
+        }
+    }
+
+    #[test]
+    fn acc_edits_are_optional() {
+        let program = get_puzzle_example();
+
+        assert!(analyse(&program, RepairOptions::default()).unwrap().acc_edits.is_empty());
+
+        // The run loops with acc at 5, having been through the accs at 1, 6 and 3.
+        let analysis = analyse(&program, RepairOptions { acc_operand: Some(10) }).unwrap();
+        assert_eq!(analysis.fixes.len(), 1);
+        assert_eq!(
+            analysis.acc_edits,
+            vec![
+                AccEdit { index: 1, original: Instruction::Acc(1), replacement: Instruction::Acc(10), accumulator: 14 },
+                AccEdit { index: 6, original: Instruction::Acc(1), replacement: Instruction::Acc(10), accumulator: 14 },
+                AccEdit { index: 3, original: Instruction::Acc(3), replacement: Instruction::Acc(10), accumulator: 12 },
+            ]
+        );
+
+        for edit in &analysis.acc_edits {
+            let mut edited = program.clone();
+            edited.set(edit.index, edit.replacement);
+            let state = run_program_until_terminated(&ProgramState::new(), &edited);
+            assert_eq!(state.termination, Some(TerminationReason::Looped));
+            assert_eq!(state.accumulator, edit.accumulator);
+        }
+
+        let terminating = decode(&["acc +3", "nop +0", "acc -1"]);
+        let analysis = analyse(&terminating, RepairOptions { acc_operand: Some(0) }).unwrap();
+        assert_eq!(analysis.acc_edits.iter().map(|edit| edit.accumulator).collect::<Vec<i32>>(), vec![-1, 3]);
+        assert!(describe_fixes(&analysis).starts_with("The program already terminates.\n\nEditing an acc"));
+    }
+
+    #[test]
+    fn matches_brute_force_on_input() {
+        let program = load_program("inputs/day8.txt").unwrap();
+        let analysis = analyse(&program, RepairOptions::default()).unwrap();
+
+        let brute_force: Vec<usize> = (0..program.len())
+            .filter(|idx| program.instructions()[*idx].flipped() != program.instructions()[*idx])
+            .filter(|idx| run_program_until_terminated(&ProgramState::new(), &program.with_flip(*idx)).termination == Some(TerminationReason::Finished))
+            .collect();
+
+        assert_eq!(analysis.fixes.iter().map(|fix| fix.index).collect::<Vec<usize>>(), brute_force);
+        for fix in &analysis.fixes {
+            assert_eq!(run_program_until_terminated(&ProgramState::new(), &apply_fix(&program, fix)).accumulator, fix.accumulator);
+        }
+    }
+
+    #[test]
+    fn only_running_off_the_end_counts() {
+        // Getting past the jmp only leads to the hlt, which stops without finishing.
+        let halting = decode(&["nop +0", "jmp -1", "hlt"]);
+        let analysis = analyse(&halting, RepairOptions::default()).unwrap();
+
+        assert_eq!(analysis.reaches_end, vec![None, None, None]);
+        assert!(analysis.fixes.is_empty());
+        assert_eq!(analysis.finishes_with, None);
+
+        // The second acc overflows before the jmp is ever reached.
+        let overflowing = decode(&["acc +2147483647", "acc +1", "jmp -2"]);
+        let analysis = analyse(&overflowing, RepairOptions::default()).unwrap();
+
+        assert_eq!(analysis.original_path, vec![0, 1]);
+        assert!(analysis.fixes.is_empty());
+
+        // As it is, the run would go past i32::MAX on the way to the end, but not once the acc -5
+        // is let in first.
+        let analysis = analyse(&decode(&["jmp +2", "acc -5", "acc +2147483647", "acc +1", "acc -10"]), RepairOptions::default()).unwrap();
+        assert_eq!(analysis.reaches_end[0], Some(2147483638));
+        assert_eq!(analysis.finishes_with, None);
+        assert_eq!(analysis.fixes, vec![Fix { index: 0, original: Instruction::Jmp(2), replacement: Instruction::Nop(2), accumulator: 2147483633 }]);
+    }
+
+    #[test]
+    fn conditional_programs_are_refused() {
+        assert!(analyse(&decode(&["jz +2", "nop +0"]), RepairOptions::default()).is_err());
+    }
+}
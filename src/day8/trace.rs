@@ -0,0 +1,241 @@
+// Day 8: Handheld Halting - Traces
+// A record of every instruction a run executes, for writing out and for lining two runs
+// up against each other (say, the broken program and its fix) to see where they part.
+use super::program::{Instruction, Program, TerminationReason};
+use super::ProgramState;
+use serde_json::json;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    pub step: usize,
+    pub pc: i32,
+    pub instruction: Instruction,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+impl TraceStep {
+    // Same work at the same place, whichever step number it happened at.
+    fn same_as(&self, other: &TraceStep) -> bool {
+        (self.pc, self.instruction, self.acc_before, self.acc_after) == (other.pc, other.instruction, other.acc_before, other.acc_after)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    pub termination: Option<TerminationReason>,
+}
+
+impl Trace {
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn to_json_lines(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| {
+                json!({
+                    "step": step.step,
+                    "pc": step.pc,
+                    "instruction": step.instruction.to_string(),
+                    "acc_before": step.acc_before,
+                    "acc_after": step.acc_after,
+                })
+                .to_string()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,pc,instruction,acc_before,acc_after\n");
+        for step in &self.steps {
+            csv.push_str(&format!("{},{},{},{},{}\n", step.step, step.pc, step.instruction, step.acc_before, step.acc_after));
+        }
+        csv
+    }
+}
+
+// Runs like run_program_until_terminated, keeping every step along the way.
+pub fn record(current_state: &ProgramState, program: &Program) -> (ProgramState, Trace) {
+    let mut state = current_state.clone();
+    let mut trace = Trace::default();
+
+    while !state.terminated() {
+        let (pc, acc_before) = (state.program_counter, state.accumulator);
+
+        // Finishing, looping and leaving the program all stop before anything runs.
+        if state.step(program) {
+            trace.steps.push(TraceStep { step: trace.steps.len(), pc, instruction: program.get(pc).unwrap(), acc_before, acc_after: state.accumulator });
+        }
+    }
+
+    trace.termination = state.termination;
+    (state, trace)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceDiff {
+    // How many steps the two runs share before they split.
+    pub common: usize,
+    // Where the two sides start doing the same work again, as (left, right) step
+    // numbers, if they ever do.
+    pub rejoin: Option<(usize, usize)>,
+    pub left_termination: Option<TerminationReason>,
+    pub right_termination: Option<TerminationReason>,
+}
+
+impl TraceDiff {
+    pub fn identical(&self, left: &Trace, right: &Trace) -> bool {
+        self.common == left.len() && self.common == right.len() && self.left_termination == self.right_termination
+    }
+}
+
+pub fn diff(left: &Trace, right: &Trace) -> TraceDiff {
+    let common = left.steps.iter().zip(&right.steps).take_while(|(a, b)| a.same_as(b)).count();
+
+    // Earliest point after the split where both sides run the same instruction from the
+    // same accumulator. With jz a side can come back to a pc, so only its first visit from
+    // each accumulator is kept.
+    let mut left_at: HashMap<(i32, i32), &TraceStep> = HashMap::new();
+    for step in &left.steps[common..] {
+        left_at.entry((step.pc, step.acc_before)).or_insert(step);
+    }
+    let rejoin = right.steps[common..]
+        .iter()
+        .filter_map(|step| left_at.get(&(step.pc, step.acc_before)).filter(|other| other.same_as(step)).map(|other| (other.step, step.step)))
+        .min_by_key(|(left_step, right_step)| left_step + right_step);
+
+    TraceDiff { common, rejoin, left_termination: left.termination, right_termination: right.termination }
+}
+
+fn describe_step(step: Option<&TraceStep>) -> String {
+    match step {
+        Some(step) => format!("{:>4}: {:<8} {:>6} -> {:<6}", step.pc, step.instruction.to_string(), step.acc_before, step.acc_after),
+        None => "-".to_string(),
+    }
+}
+
+fn describe_termination(termination: Option<TerminationReason>) -> String {
+    termination.map_or("still running".to_string(), |reason| reason.to_string())
+}
+
+// The two runs side by side around where they split, with `context` steps either side.
+pub fn render_diff(left: &Trace, right: &Trace, context: usize) -> String {
+    let result = diff(left, right);
+    if result.identical(left, right) {
+        return format!("Both runs take the same {} steps and {}.", left.len(), describe_termination(left.termination));
+    }
+
+    let mut lines = vec![format!("The runs agree for {} steps, then split:", result.common)];
+    for step in result.common.saturating_sub(context)..(result.common + context + 1).min(left.len().max(right.len())) {
+        let marker = if step < result.common { " " } else { "*" };
+        lines.push(format!("{} {:>5} | {:<30} | {}", marker, step, describe_step(left.steps.get(step)), describe_step(right.steps.get(step))));
+    }
+
+    match result.rejoin {
+        Some((left_step, right_step)) => lines.push(format!("They line up again at left step {}, right step {}.", left_step, right_step)),
+        None => lines.push("They never line up again.".to_string()),
+    }
+    lines.push(format!(
+        "Left {} after {} steps, right {} after {} steps.",
+        describe_termination(result.left_termination),
+        left.len(),
+        describe_termination(result.right_termination),
+        right.len()
+    ));
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tracetests {
+    use super::*;
+    use crate::day8::assembler::InstructionSet;
+    use crate::day8::{load_program, program_autofix, run_program_until_terminated};
+
+    fn decode(lines: &[&str]) -> Program {
+        Program::decode_with(&lines.iter().map(|line| line.to_string()).collect::<Vec<String>>(), &InstructionSet::extended()).unwrap()
+    }
+
+    fn get_puzzle_example() -> Program {
+        decode(&["nop +0", "acc +1", "jmp +4", "acc +3", "jmp -3", "acc -99", "acc +1", "jmp -4", "acc +6"])
+    }
+
+    #[test]
+    fn recording_matches_a_plain_run() {
+        let program = get_puzzle_example();
+        let (state, trace) = record(&ProgramState::new(), &program);
+
+        assert_eq!(state, run_program_until_terminated(&ProgramState::new(), &program));
+        assert_eq!(trace.termination, Some(TerminationReason::Looped));
+        assert_eq!(trace.steps.iter().map(|step| step.pc).collect::<Vec<i32>>(), vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(trace.steps[5], TraceStep { step: 5, pc: 3, instruction: Instruction::Acc(3), acc_before: 2, acc_after: 5 });
+
+        let (_, halted) = record(&ProgramState::new(), &decode(&["acc +2", "hlt"]));
+        assert_eq!(halted.steps.last().unwrap().instruction, Instruction::Hlt);
+    }
+
+    #[test]
+    fn traces_export() {
+        let (_, trace) = record(&ProgramState::new(), &decode(&["acc +1", "jmp -1"]));
+
+        assert_eq!(trace.to_csv(), "step,pc,instruction,acc_before,acc_after\n0,0,acc +1,0,1\n1,1,jmp -1,1,1\n");
+
+        let lines: Vec<serde_json::Value> = trace.to_json_lines().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["instruction"], "jmp -1");
+        assert_eq!(lines[1]["acc_before"], 1);
+    }
+
+    #[test]
+    fn diff_finds_where_the_fix_changes_things() {
+        let program = get_puzzle_example();
+        let (fixed, fixed_at) = program_autofix(&program).unwrap();
+        let (_, original) = record(&ProgramState::new(), &program);
+        let (_, repaired) = record(&ProgramState::new(), &fixed);
+
+        let result = diff(&original, &repaired);
+        assert_eq!(result.common, 4);
+        assert_eq!(original.steps[4].pc, fixed_at);
+        assert_eq!(result.right_termination, Some(TerminationReason::Finished));
+        assert!(!result.identical(&original, &repaired));
+
+        let rendered = render_diff(&original, &repaired, 1);
+        assert!(rendered.starts_with("The runs agree for 4 steps"));
+        assert!(rendered.contains("jmp -4"));
+        assert!(rendered.contains("nop -4"));
+
+        assert!(diff(&original, &original).identical(&original, &original));
+    }
+
+    #[test]
+    fn diverging_runs_can_rejoin() {
+        // Both go round the acc at 3 with nothing added, one via a nop and one via a jmp.
+        let (_, left) = record(&ProgramState::new(), &decode(&["nop +0", "nop +2", "nop +0", "acc +1"]));
+        let (_, right) = record(&ProgramState::new(), &decode(&["nop +0", "jmp +2", "nop +0", "acc +1"]));
+
+        let result = diff(&left, &right);
+        assert_eq!(result.common, 1);
+        assert_eq!(result.rejoin, Some((3, 2)));
+    }
+
+    #[test]
+    fn puzzle_traces_diff() {
+        let program = load_program("inputs/day8.txt").unwrap();
+        let (fixed, fixed_at) = program_autofix(&program).unwrap();
+        let (_, original) = record(&ProgramState::new(), &program);
+        let (state, repaired) = record(&ProgramState::new(), &fixed);
+
+        let result = diff(&original, &repaired);
+        assert_eq!(original.steps[result.common].pc, fixed_at);
+        assert_eq!(repaired.steps.last().unwrap().acc_after, state.accumulator);
+    }
+}
@@ -0,0 +1,340 @@
+// Day 9: Encoding Error - Synthetic Streams
+// Writes XMAS streams with invalid numbers at chosen places and, optionally, a planted run
+// adding up to a chosen target, plus a manifest of the answers to expect. Like the day 4
+// generator, the manifest comes from how the stream was built: anything that breaks the
+// encoding is checked against the window as it's generated, by the slow obvious method.
+// Ranges are the exception. An earlier run can happen to hit the same target as the planted
+// one, and the puzzle wants the earliest, so those come from ranges::find_range.
+use super::ranges::{self, ContiguousRange};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlantedRange {
+    pub start: usize,
+    pub len: usize,
+    pub target: i64,
+}
+
+pub struct StreamConfig {
+    pub length: usize,
+    pub window: usize,
+    pub seed: u64,
+    // Positions to put an invalid number at; all of them must be past the preamble.
+    pub invalid_positions: Vec<usize>,
+    pub planted_range: Option<PlantedRange>,
+    // Negative numbers keep long streams small. Without them every number is at least
+    // the sum of two before it, so the values keep growing and long streams run out of room.
+    pub allow_negative: bool,
+    // How big the preamble's numbers can get.
+    pub magnitude: i64,
+}
+
+impl StreamConfig {
+    pub fn new(length: usize, window: usize, seed: u64) -> StreamConfig {
+        StreamConfig { length, window, seed, invalid_positions: Vec::new(), planted_range: None, allow_negative: false, magnitude: 50 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum InvalidReason {
+    Injected,
+    // The last number of a planted range, forced to make the range add up, that happened
+    // not to be a sum of two before it.
+    PlantedRange,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExpectedInvalid {
+    pub index: usize,
+    pub value: i64,
+    pub reason: InvalidReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExpectedRange {
+    pub start: usize,
+    // Inclusive.
+    pub end: usize,
+    pub target: i64,
+    pub min: i64,
+    pub max: i64,
+    pub weakness: i64,
+}
+
+impl ExpectedRange {
+    fn new(range: &ContiguousRange, target: i64) -> ExpectedRange {
+        ExpectedRange { start: range.start, end: range.end, target, min: range.min, max: range.max, weakness: range.weakness() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StreamManifest {
+    pub length: usize,
+    pub window: usize,
+    pub seed: u64,
+    pub invalid: Vec<ExpectedInvalid>,
+    pub first_invalid: Option<i64>,
+    // The second star's answer: the earliest run adding up to first_invalid.
+    pub first_invalid_range: Option<ExpectedRange>,
+    // The earliest run adding up to the planted range's target. That's the planted range
+    // itself unless an earlier run got there first.
+    pub planted_range: Option<ExpectedRange>,
+}
+
+pub struct GeneratedStream {
+    pub numbers: Vec<i64>,
+    pub manifest: StreamManifest,
+}
+
+impl GeneratedStream {
+    pub fn text(&self) -> String {
+        self.numbers.iter().map(|number| number.to_string() + "\n").collect()
+    }
+
+    pub fn manifest_json(&self) -> String {
+        serde_json::to_string_pretty(&self.manifest).unwrap()
+    }
+
+    pub fn write(&self, stream_path: &str, manifest_path: &str) -> io::Result<()> {
+        fs::write(stream_path, self.text())?;
+        fs::write(manifest_path, self.manifest_json())
+    }
+}
+
+// Whether two different entries in window add up to value, checking every pair.
+fn is_pair_sum(window: &[i64], value: i64) -> bool {
+    (0..window.len()).any(|first| (first + 1..window.len()).any(|second| window[first].checked_add(window[second]) == Some(value)))
+}
+
+fn valid_number(window: &[i64], config: &StreamConfig, rng: &mut StdRng) -> Result<i64, String> {
+    if config.allow_negative {
+        // The pair landing closest to a random value in range, so the window keeps a spread
+        // of both signs rather than drifting off one way. Two pointers over the sorted window.
+        let wanted = rng.gen_range(-config.magnitude..=config.magnitude) as i128;
+        let mut sorted: Vec<i128> = window.iter().map(|number| *number as i128).collect();
+        sorted.sort_unstable();
+
+        let (mut low, mut high) = (0, sorted.len() - 1);
+        let mut best = sorted[low] + sorted[high];
+        while low < high {
+            let sum = sorted[low] + sorted[high];
+            if (sum - wanted).abs() < (best - wanted).abs() {
+                best = sum;
+            }
+            if sum < wanted {
+                low += 1;
+            } else {
+                high -= 1;
+            }
+        }
+        return i64::try_from(best).map_err(|_| "The numbers outgrew an i64".to_string());
+    }
+
+    // Two of the smallest few, to grow as slowly as the encoding allows.
+    let mut smallest: Vec<usize> = (0..window.len()).collect();
+    smallest.sort_by_key(|idx| window[*idx]);
+    smallest.truncate(4);
+    let pair: Vec<&usize> = smallest.choose_multiple(rng, 2).collect();
+    window[*pair[0]]
+        .checked_add(window[*pair[1]])
+        .ok_or_else(|| "The numbers outgrew an i64; try a shorter stream, a wider window or allowing negatives".to_string())
+}
+
+fn invalid_number(window: &[i64], config: &StreamConfig, rng: &mut StdRng) -> i64 {
+    let largest = *window.iter().max().unwrap();
+    let low = if config.allow_negative { -3 * config.magnitude } else { 1 };
+    let high = largest.saturating_mul(2).max(low + 1);
+
+    for _ in 0..100 {
+        let value = rng.gen_range(low..=high);
+        if !is_pair_sum(window, value) {
+            return value;
+        }
+    }
+
+    // No two entries add up to more than twice the largest. Should that not fit, every
+    // pair sum is at least twice the smallest, so anything below that will do.
+    match largest.checked_mul(2).and_then(|doubled| doubled.checked_add(1)) {
+        Some(value) => value,
+        None => window.iter().min().unwrap().saturating_mul(2).saturating_sub(1),
+    }
+}
+
+pub fn generate_stream(config: &StreamConfig) -> Result<GeneratedStream, String> {
+    if config.window < 2 {
+        return Err(format!("A window of {} can't hold a pair of numbers", config.window));
+    }
+    if config.length < config.window {
+        return Err(format!("A stream of {} numbers doesn't fill a window of {}", config.length, config.window));
+    }
+    if config.magnitude < 1 {
+        return Err("The magnitude has to be at least 1".to_string());
+    }
+    if let Some(position) = config.invalid_positions.iter().find(|position| **position < config.window || **position >= config.length) {
+        return Err(format!("Position {} isn't between the preamble and the end of the stream", position));
+    }
+
+    // Where the planted range's last number goes; it's set to whatever makes the range add up.
+    let forced = match config.planted_range {
+        Some(range) if range.len < 2 => return Err("A planted range needs at least two numbers".to_string()),
+        Some(range) if range.start + range.len > config.length => return Err(format!("A range of {} from {} runs off the end of the stream", range.len, range.start)),
+        Some(range) if config.invalid_positions.contains(&(range.start + range.len - 1)) => {
+            return Err(format!("Position {} ends the planted range, so it can't be an injected invalid number too", range.start + range.len - 1))
+        }
+        Some(range) => Some(range.start + range.len - 1),
+        None => None,
+    };
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut numbers: Vec<i64> = Vec::with_capacity(config.length);
+    let mut invalid = Vec::new();
+
+    for index in 0..config.length {
+        let window = &numbers[index.saturating_sub(config.window)..];
+
+        let value = if Some(index) == forced {
+            let range = config.planted_range.unwrap();
+            let others = numbers[range.start..index].iter().try_fold(0i64, |sum, number| sum.checked_add(*number));
+            let value = others.and_then(|others| range.target.checked_sub(others)).ok_or_else(|| "The planted range's numbers outgrew an i64".to_string())?;
+            if !config.allow_negative && value < 0 {
+                return Err(format!("The numbers before the end of the planted range already add up to more than {}", range.target));
+            }
+            if index >= config.window && !is_pair_sum(window, value) {
+                invalid.push(ExpectedInvalid { index, value, reason: InvalidReason::PlantedRange });
+            }
+            value
+        } else if index < config.window {
+            let low = if config.allow_negative { -config.magnitude } else { 1 };
+            rng.gen_range(low..=config.magnitude)
+        } else if config.invalid_positions.contains(&index) {
+            let value = invalid_number(window, config, &mut rng);
+            invalid.push(ExpectedInvalid { index, value, reason: InvalidReason::Injected });
+            value
+        } else {
+            valid_number(window, config, &mut rng)?
+        };
+
+        numbers.push(value);
+    }
+
+    let expected_range = |target: i64| ranges::find_range(&numbers, target).ok().map(|range| ExpectedRange::new(&range, target));
+    let first_invalid = invalid.first().map(|number| number.value);
+
+    let manifest = StreamManifest {
+        length: config.length,
+        window: config.window,
+        seed: config.seed,
+        first_invalid,
+        first_invalid_range: first_invalid.and_then(expected_range),
+        planted_range: config.planted_range.and_then(|range| expected_range(range.target)),
+        invalid,
+    };
+
+    Ok(GeneratedStream { numbers, manifest })
+}
+
+#[cfg(test)]
+mod generatortests {
+    use super::*;
+    use crate::day9::validator;
+    use std::io::Cursor;
+
+    fn invalid_pairs(stream: &GeneratedStream) -> Vec<(usize, i64)> {
+        stream.manifest.invalid.iter().map(|number| (number.index, number.value)).collect()
+    }
+
+    #[test]
+    fn generation_is_repeatable() {
+        let config = StreamConfig::new(200, 5, 2020);
+
+        assert_eq!(generate_stream(&config).unwrap().numbers, generate_stream(&config).unwrap().numbers);
+        assert_ne!(generate_stream(&config).unwrap().numbers, generate_stream(&StreamConfig::new(200, 5, 2021)).unwrap().numbers);
+    }
+
+    #[test]
+    fn validator_finds_exactly_the_injected_numbers() {
+        for (window, seed) in [(2, 1), (5, 2), (25, 3), (40, 4)] {
+            let mut config = StreamConfig::new(window * 6, window, seed);
+            config.invalid_positions = vec![window, window + 3, window * 4, window * 6 - 1];
+            let stream = generate_stream(&config).unwrap();
+
+            let found = validator::validate_reader(Cursor::new(stream.text()), window).unwrap();
+            assert_eq!(found.iter().map(|number| (number.index, number.value)).collect::<Vec<(usize, i64)>>(), invalid_pairs(&stream), "window {}", window);
+            assert_eq!(stream.manifest.invalid.len(), 4);
+        }
+    }
+
+    #[test]
+    fn long_streams_with_negatives() {
+        let mut config = StreamConfig::new(100_000, 25, 9);
+        config.allow_negative = true;
+        config.magnitude = 1_000;
+        config.invalid_positions = vec![25, 5_000, 99_999];
+        let stream = generate_stream(&config).unwrap();
+
+        let found = validator::find_invalid_numbers(stream.numbers.iter().copied(), 25).unwrap();
+        assert_eq!(found.iter().map(|number| (number.index, number.value)).collect::<Vec<(usize, i64)>>(), invalid_pairs(&stream));
+        assert!(stream.numbers.iter().all(|number| number.abs() <= 3_000));
+
+        // Without negatives the numbers keep growing, and eventually can't.
+        assert!(generate_stream(&StreamConfig::new(100_000, 25, 9)).is_err());
+    }
+
+    #[test]
+    fn planted_range_is_found() {
+        let mut config = StreamConfig::new(300, 25, 7);
+        config.planted_range = Some(PlantedRange { start: 100, len: 12, target: 1 << 40 });
+        let stream = generate_stream(&config).unwrap();
+        let expected = stream.manifest.planted_range.clone().unwrap();
+
+        assert_eq!(stream.numbers[100..112].iter().sum::<i64>(), 1 << 40);
+        assert_eq!((expected.start, expected.end), (100, 111));
+        let found = ranges::find_range(&stream.numbers, 1 << 40).unwrap();
+        assert_eq!((found.start, found.end, found.weakness()), (expected.start, expected.end, expected.weakness));
+
+        let first_invalid = stream.manifest.first_invalid.unwrap();
+        let found = ranges::find_range(&stream.numbers, first_invalid).ok();
+        assert_eq!(found.map(|range| (range.start, range.end, range.weakness())), stream.manifest.first_invalid_range.as_ref().map(|range| (range.start, range.end, range.weakness)));
+
+        // The forced number almost certainly breaks the encoding, and the manifest says so.
+        let found = validator::find_invalid_numbers(stream.numbers.iter().copied(), 25).unwrap();
+        assert_eq!(found.iter().map(|number| (number.index, number.value)).collect::<Vec<(usize, i64)>>(), invalid_pairs(&stream));
+    }
+
+    #[test]
+    fn bad_configs_are_refused() {
+        let mut config = StreamConfig::new(100, 25, 1);
+        config.invalid_positions = vec![10];
+        assert!(generate_stream(&config).is_err());
+
+        let mut config = StreamConfig::new(100, 25, 1);
+        config.planted_range = Some(PlantedRange { start: 90, len: 20, target: 5 });
+        assert!(generate_stream(&config).is_err());
+
+        // Far too small for the numbers already in the range.
+        let mut config = StreamConfig::new(100, 5, 1);
+        config.planted_range = Some(PlantedRange { start: 50, len: 10, target: 5 });
+        assert!(generate_stream(&config).is_err());
+
+        assert!(generate_stream(&StreamConfig::new(3, 5, 1)).is_err());
+        assert!(generate_stream(&StreamConfig::new(10, 1, 1)).is_err());
+    }
+
+    #[test]
+    fn manifest_serialises() {
+        let mut config = StreamConfig::new(50, 5, 3);
+        config.invalid_positions = vec![20];
+        let manifest: serde_json::Value = serde_json::from_str(&generate_stream(&config).unwrap().manifest_json()).unwrap();
+
+        assert_eq!(manifest["invalid"][0]["index"], 20);
+        assert_eq!(manifest["invalid"][0]["reason"], "Injected");
+        assert!(manifest["planted_range"].is_null());
+        assert!(manifest.get("first_invalid_range").is_some());
+    }
+}
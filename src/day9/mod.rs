@@ -4,7 +4,11 @@ use super::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use circular_queue::CircularQueue;
-use itertools::Itertools;
+use cursive::views::{EditView, LinearLayout};
+
+pub mod generator;
+pub mod ranges;
+pub mod validator;
 
 pub fn display_day_menu(s: &mut Cursive) {
     let menu = SelectView::<i32>::new()
@@ -20,6 +24,8 @@ fn populate_menu(s: &mut Cursive) {
     s.call_on_name("day_menu", |view: &mut SelectView<i32>| {
         view.add_item("1st Star ⭐", 0);
         view.add_item("2nd Star 🌟", 1);
+        view.add_item("All Invalid Numbers 🔎", 2);
+        view.add_item("Generate Test Stream 🧪", 3);
         view.add_item("Back", 999);
     });
 }
@@ -32,6 +38,12 @@ fn menu_selection(s: &mut Cursive, selection: &i32) {
         1 => {
             second_star(s);
         }
+        2 => {
+            invalid_numbers_prompt(s);
+        }
+        3 => {
+            generate_test_stream(s);
+        }
         999 => {
             s.pop_layer();
         }
@@ -48,7 +60,10 @@ pub fn first_star(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day9.txt").unwrap());
             let inputs: Vec<i64> = bufreader.lines().map(|line| line.unwrap().parse().unwrap()).collect();
 
-            Ok(format!("The first bad number in this stream is {}!", xmas_encoding_find_first_invalid_number(&inputs, 25)))
+            match xmas_encoding_find_first_invalid_number(&inputs, validator::DEFAULT_WINDOW as i64) {
+                Ok(first_invalid_number) => Ok(format!("The first bad number in this stream is {}!", first_invalid_number)),
+                Err(message) => Ok(message),
+            }
         },
         TextView::new,
     )
@@ -73,9 +88,14 @@ pub fn second_star(s: &mut Cursive) {
             let bufreader = BufReader::new(File::open("inputs/day9.txt").unwrap());
             let inputs: Vec<i64> = bufreader.lines().map(|line| line.unwrap().parse().unwrap()).collect();
 
-            let first_invalid_number = xmas_encoding_find_first_invalid_number(&inputs, 25);
-            let encryption_weakness = xmas_encoding_find_contiguous_set_sum_to(first_invalid_number, &inputs);
-            Ok(format!("The encryption weakness with target value {} is: {:?}! 😎", first_invalid_number, encryption_weakness))
+            let first_invalid_number = match xmas_encoding_find_first_invalid_number(&inputs, validator::DEFAULT_WINDOW as i64) {
+                Ok(first_invalid_number) => first_invalid_number,
+                Err(message) => return Ok(message),
+            };
+            match ranges::find_range(&inputs, first_invalid_number) {
+                Ok(range) => Ok(format!("The encryption weakness with target value {} is: {}! 😎\n\n{}", first_invalid_number, range.weakness(), ranges::describe_range(&range))),
+                Err(message) => Ok(message),
+            }
         },
         TextView::new,
     )
@@ -91,48 +111,131 @@ pub fn second_star(s: &mut Cursive) {
     );
 }
 
-pub fn valid_next_number(next_num: i64, queue: &CircularQueue<i64>) -> bool {
-    queue.iter().combinations(2).filter(|pair| (pair[0] + pair[1]) == next_num).count() > 0
+pub fn invalid_numbers_prompt(s: &mut Cursive) {
+    let prompt = LinearLayout::vertical()
+        .child(TextView::new("How many numbers to check against?"))
+        .child(EditView::new().content(validator::DEFAULT_WINDOW.to_string()).with_name("xmas_window").fixed_width(10));
+
+    s.add_layer(
+        Dialog::around(prompt)
+            .title("All Invalid Numbers 🔎")
+            .button("Check", |s| {
+                let window = s.call_on_name("xmas_window", |view: &mut EditView| view.get_content()).unwrap();
+                match window.trim().parse::<usize>() {
+                    Ok(window) => {
+                        s.pop_layer();
+                        invalid_numbers(s, window);
+                    }
+                    Err(_) => s.add_layer(Dialog::info(format!("{} isn't a window size", window))),
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
 }
 
-pub fn xmas_encoding_find_first_invalid_number(inputs: &Vec<i64>, step_size: i64) -> i64 {
-    let mut queue = CircularQueue::with_capacity(step_size as usize);
+pub fn invalid_numbers(s: &mut Cursive, window: usize) {
+    let async_view = AsyncView::new_with_bg_creator(
+        s,
+        move || {
+            let file = match File::open("inputs/day9.txt") {
+                Ok(file) => file,
+                Err(err) => return Ok(format!("Couldn't read inputs/day9.txt: {}", err)),
+            };
+            match validator::validate_reader(BufReader::new(file), window) {
+                Ok(invalid) => Ok(validator::describe_invalid_numbers(&invalid)),
+                Err(message) => Ok(message),
+            }
+        },
+        |text| ScrollView::new(TextView::new(text)),
+    )
+    .with_height(15)
+    .with_width(40);
 
-    // Preamble.
-    for idx in 0 .. step_size {
-        queue.push(inputs[idx as usize]);
-    }
+    s.add_layer(
+        Dialog::around(async_view)
+            .title("All Invalid Numbers 🔎")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn generate_test_stream(s: &mut Cursive) {
+    let mut config = generator::StreamConfig::new(1000, validator::DEFAULT_WINDOW, 2020);
+    config.invalid_positions = vec![100, 500, 999];
+    config.planted_range = Some(generator::PlantedRange { start: 200, len: 17, target: 1_000_000_000 });
+
+    let result = generator::generate_stream(&config)
+        .and_then(|stream| {
+            std::fs::create_dir_all("out")
+                .and_then(|_| stream.write("out/day9_generated.txt", "out/day9_generated_manifest.json"))
+                .map(|_| stream)
+                .map_err(|err| format!("Couldn't write the stream: {}", err))
+        });
+
+    let message = match result {
+        Ok(stream) => format!(
+            "Wrote {} numbers ({} invalid) to out/day9_generated.txt",
+            stream.numbers.len(),
+            stream.manifest.invalid.len()
+        ),
+        Err(message) => message,
+    };
+
+    s.add_layer(
+        Dialog::around(TextView::new(message))
+            .title("Generate Test Stream 🧪")
+            .button("Neat!", |s| {
+                s.pop_layer();
+            }),
+    );
+}
 
-    // Find first invalid number.
-    let mut first_invalid_number = 0;
-    for idx in step_size .. inputs.len() as i64 {
-        first_invalid_number = inputs[idx as usize];
-        if !valid_next_number(first_invalid_number, &queue) { 
-            break;
+// validate [--window N] [FILE], reading stdin when there's no file or it's -.
+pub fn run_command(args: &[String]) -> Result<String, String> {
+    match args {
+        [command, rest @ ..] if command == "validate" => {
+            let (window, rest) = match rest {
+                [flag, window, rest @ ..] if flag == "--window" => (window.parse().map_err(|_| format!("{} isn't a window size", window))?, rest),
+                _ => (validator::DEFAULT_WINDOW, rest),
+            };
+            let invalid = match rest {
+                [] => validator::validate_reader(std::io::stdin().lock(), window)?,
+                [path] if path == "-" => validator::validate_reader(std::io::stdin().lock(), window)?,
+                [path] => validator::validate_reader(BufReader::new(File::open(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?), window)?,
+                _ => return Err("usage: day9 validate [--window N] [FILE]".to_string()),
+            };
+            Ok(validator::describe_invalid_numbers(&invalid))
         }
-        queue.push(first_invalid_number);
+        _ => Err("usage: day9 validate [--window N] [FILE]".to_string()),
     }
-
-    first_invalid_number
 }
 
-pub fn xmas_encoding_find_contiguous_set_sum_to(target: i64, inputs:&Vec<i64>) -> Result<i64, String> {
-    let mut current_sum = 0;
-    for set_start in 0 .. inputs.len() {
-        current_sum = inputs[set_start];
+pub fn valid_next_number(next_num: i64, queue: &CircularQueue<i64>) -> bool {
+    // A validator wide enough to hold the whole queue, so the pair check is the streaming one.
+    let mut checker = validator::XmasValidator::new(queue.len().max(2)).unwrap();
+    for num in queue.iter() {
+        checker.push(*num);
+    }
+    checker.is_sum_of_pair(next_num)
+}
 
-        for set_end in set_start+1 .. inputs.len() {
-            current_sum += inputs[set_end];
+pub fn xmas_encoding_find_first_invalid_number(inputs: &Vec<i64>, step_size: i64) -> Result<i64, String> {
+    if step_size < 0 {
+        return Err(format!("A window of {} can't hold a pair of numbers", step_size));
+    }
+    let invalid = validator::find_invalid_numbers(inputs.iter().copied(), step_size as usize)?;
 
-            if current_sum == target {
-                // Found contiguous segment! Let's take a slice, and add the smallest & largest values together to get our result.
-                let sliced_input = &inputs[set_start .. set_end+1];
-                return Ok(sliced_input.iter().min().unwrap() + sliced_input.iter().max().unwrap());
-            }
-        }
+    match invalid.first() {
+        Some(number) => Ok(number.value),
+        None => Err("Every number checks out".to_string()),
     }
+}
 
-    Err(format!("Couldn't find it... {} -> {}", inputs.len(), current_sum))
+pub fn xmas_encoding_find_contiguous_set_sum_to(target: i64, inputs:&Vec<i64>) -> Result<i64, String> {
+    ranges::find_range(inputs, target).map(|range| range.weakness())
 }
 
 
@@ -162,6 +265,9 @@ mod day9tests {
         assert_eq!(valid_next_number(65, &test_queue), false);
         assert_eq!(valid_next_number(64, &test_queue), true);
         assert_eq!(valid_next_number(66, &test_queue), true);
+
+        test_queue.push(i64::MAX);
+        assert!(!valid_next_number(i64::MIN, &test_queue));
     }
 
     #[test]
@@ -189,7 +295,12 @@ mod day9tests {
             576,
         ];
 
-        assert_eq!(xmas_encoding_find_first_invalid_number(&test_inputs, 5), 127);
+        assert_eq!(xmas_encoding_find_first_invalid_number(&test_inputs, 5), Ok(127));
+        assert_eq!(xmas_encoding_find_first_invalid_number(&test_inputs, 1), Err("A window of 1 can't hold a pair of numbers".to_string()));
+        assert!(xmas_encoding_find_first_invalid_number(&test_inputs, -5).is_err());
+
+        assert_eq!(xmas_encoding_find_first_invalid_number(&test_inputs[..14].to_vec(), 5), Err("Every number checks out".to_string()));
+        assert_eq!(xmas_encoding_find_first_invalid_number(&test_inputs[..3].to_vec(), 5), Err("Every number checks out".to_string()));
     }
 
     #[test]
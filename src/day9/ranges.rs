@@ -0,0 +1,232 @@
+// Day 9: Encoding Error - Contiguous Ranges
+// Finds runs of two or more consecutive numbers adding up to a target. Streams with no
+// negatives use a sliding window, as the sum only grows as the run does. Anything else
+// falls back to prefix sums: a run adds up to the target exactly when the prefix sum at
+// its end is the target more than the prefix sum at its start.
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContiguousRange {
+    pub start: usize,
+    // Inclusive.
+    pub end: usize,
+    pub members: Vec<i64>,
+    pub min: i64,
+    pub max: i64,
+}
+
+impl ContiguousRange {
+    fn new(inputs: &[i64], start: usize, end: usize) -> ContiguousRange {
+        let members = inputs[start..=end].to_vec();
+        let min = *members.iter().min().unwrap();
+        let max = *members.iter().max().unwrap();
+        ContiguousRange { start, end, members, min, max }
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    // The puzzle's answer: smallest plus largest.
+    pub fn weakness(&self) -> i64 {
+        self.min + self.max
+    }
+}
+
+fn not_found(target: i64) -> String {
+    format!("No run of two or more numbers adds up to {}", target)
+}
+
+// The run with the earliest start, and of those the shortest.
+pub fn find_range(inputs: &[i64], target: i64) -> Result<ContiguousRange, String> {
+    let found = if inputs.iter().all(|value| *value >= 0) { sliding_window_first(inputs, target) } else { prefix_sum_first(inputs, target) };
+    found.map(|(start, end)| ContiguousRange::new(inputs, start, end)).ok_or_else(|| not_found(target))
+}
+
+// Every run, ordered by start and then end.
+pub fn find_all_ranges(inputs: &[i64], target: i64) -> Vec<ContiguousRange> {
+    let found = if inputs.iter().all(|value| *value >= 0) { sliding_window_all(inputs, target) } else { prefix_sum_all(inputs, target) };
+    found.into_iter().map(|(start, end)| ContiguousRange::new(inputs, start, end)).collect()
+}
+
+// For each start, the shortest run (of at least two) reaching the target. The end of
+// that only moves forwards as the start does, so both pointers cross the input once.
+fn sliding_window_first(inputs: &[i64], target: i64) -> Option<(usize, usize)> {
+    let target = target as i128;
+    let mut end = 0;
+    let mut sum: i128 = 0;
+
+    for start in 0..inputs.len() {
+        // sum covers start..end, exclusive of end.
+        if end <= start {
+            end = start;
+            sum = 0;
+        }
+        while end < inputs.len() && (end < start + 2 || sum < target) {
+            sum += inputs[end] as i128;
+            end += 1;
+        }
+        if end >= start + 2 && sum == target {
+            return Some((start, end - 1));
+        }
+        sum -= inputs[start] as i128;
+    }
+
+    None
+}
+
+// As above, then carrying on through any zeros, which leave the sum where it is.
+fn sliding_window_all(inputs: &[i64], target: i64) -> Vec<(usize, usize)> {
+    let target128 = target as i128;
+    let mut found = Vec::new();
+    let mut end = 0;
+    let mut sum: i128 = 0;
+
+    for start in 0..inputs.len() {
+        if end <= start {
+            end = start;
+            sum = 0;
+        }
+        while end < inputs.len() && (end < start + 2 || sum < target128) {
+            sum += inputs[end] as i128;
+            end += 1;
+        }
+        if end >= start + 2 && sum == target128 {
+            found.push((start, end - 1));
+            found.extend((end..inputs.len()).take_while(|idx| inputs[*idx] == 0).map(|idx| (start, idx)));
+        }
+        sum -= inputs[start] as i128;
+    }
+
+    found
+}
+
+// prefix[i] is the sum of everything before index i.
+fn prefix_sums(inputs: &[i64]) -> Vec<i128> {
+    let mut prefix = Vec::with_capacity(inputs.len() + 1);
+    prefix.push(0);
+    for value in inputs {
+        prefix.push(prefix.last().unwrap() + *value as i128);
+    }
+    prefix
+}
+
+fn prefix_sum_first(inputs: &[i64], target: i64) -> Option<(usize, usize)> {
+    let prefix = prefix_sums(inputs);
+    let mut positions: HashMap<i128, Vec<usize>> = HashMap::new();
+    for (idx, sum) in prefix.iter().enumerate() {
+        positions.entry(*sum).or_default().push(idx);
+    }
+
+    (0..inputs.len()).find_map(|start| {
+        let ends = positions.get(&(prefix[start] + target as i128))?;
+        // The first prefix at least two numbers on.
+        let first = ends.partition_point(|idx| *idx < start + 2);
+        ends.get(first).map(|end| (start, end - 1))
+    })
+}
+
+fn prefix_sum_all(inputs: &[i64], target: i64) -> Vec<(usize, usize)> {
+    let prefix = prefix_sums(inputs);
+    let mut starts: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut found = Vec::new();
+
+    // Starts only become available two numbers behind the end being looked at.
+    for end in 2..prefix.len() {
+        starts.entry(prefix[end - 2]).or_default().push(end - 2);
+        if let Some(matching) = starts.get(&(prefix[end] - target as i128)) {
+            found.extend(matching.iter().map(|start| (*start, end - 1)));
+        }
+    }
+
+    found.sort_unstable();
+    found
+}
+
+pub fn describe_range(range: &ContiguousRange) -> String {
+    format!(
+        "#{} to #{} ({} numbers), from {} up to {}, weakness {}",
+        range.start,
+        range.end,
+        range.len(),
+        range.min,
+        range.max,
+        range.weakness()
+    )
+}
+
+#[cfg(test)]
+mod rangestests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn get_puzzle_example() -> Vec<i64> {
+        vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576]
+    }
+
+    // The obvious way, to check the others against.
+    fn brute_force(inputs: &[i64], target: i64) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        for start in 0..inputs.len() {
+            for end in start + 1..inputs.len() {
+                if inputs[start..=end].iter().sum::<i64>() == target {
+                    found.push((start, end));
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn puzzle_example_range() {
+        let range = find_range(&get_puzzle_example(), 127).unwrap();
+
+        assert_eq!((range.start, range.end), (2, 5));
+        assert_eq!(range.members, vec![15, 25, 47, 40]);
+        assert_eq!((range.min, range.max, range.weakness()), (15, 47, 62));
+        assert_eq!(find_all_ranges(&get_puzzle_example(), 127), vec![range]);
+    }
+
+    #[test]
+    fn runs_need_two_numbers() {
+        // 127 on its own doesn't count.
+        assert_eq!(find_range(&[1, 127, 3], 127), Err("No run of two or more numbers adds up to 127".to_string()));
+        assert_eq!(find_range(&[-1, 127, 3], 127), Err("No run of two or more numbers adds up to 127".to_string()));
+        assert!(find_range(&[], 0).is_err());
+    }
+
+    #[test]
+    fn zeros_and_negatives() {
+        let zeros = [3, 0, 2, 0, 0, 5];
+        assert_eq!(find_all_ranges(&zeros, 5).iter().map(|range| (range.start, range.end)).collect::<Vec<(usize, usize)>>(), vec![(0, 2), (0, 3), (0, 4), (3, 5), (4, 5)]);
+
+        let negatives = [4, -3, 9, 1, -1, 2];
+        let range = find_range(&negatives, 10).unwrap();
+        assert_eq!((range.start, range.end, range.min, range.max), (0, 2, -3, 9));
+        assert_eq!(find_all_ranges(&negatives, 10).len(), brute_force(&negatives, 10).len());
+    }
+
+    #[test]
+    fn both_methods_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(9);
+
+        for round in 0..400 {
+            let low = if round % 2 == 0 { 0 } else { -10 };
+            let inputs: Vec<i64> = (0..rng.gen_range(0..30)).map(|_| rng.gen_range(low..=10)).collect();
+            let target = rng.gen_range(-5..=40);
+            let expected = brute_force(&inputs, target);
+
+            assert_eq!(prefix_sum_all(&inputs, target), expected, "{:?} -> {}", inputs, target);
+            assert_eq!(prefix_sum_first(&inputs, target), expected.first().copied(), "{:?} -> {}", inputs, target);
+            if low == 0 {
+                assert_eq!(sliding_window_all(&inputs, target), expected, "{:?} -> {}", inputs, target);
+                assert_eq!(sliding_window_first(&inputs, target), expected.first().copied(), "{:?} -> {}", inputs, target);
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// Day 9: Encoding Error - Streaming Validator
+// Checks XMAS numbers one at a time as they arrive, so a stream never has to be held in
+// memory. The window keeps a count of each value in it alongside the numbers themselves,
+// so checking a number is one pass over the window with a hash lookup per entry.
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+
+pub const DEFAULT_WINDOW: usize = 25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    // Still filling the window; nothing to check against yet.
+    Preamble,
+    Valid,
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidNumber {
+    // Position in the stream, counting the preamble, from 0.
+    pub index: usize,
+    pub value: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct XmasValidator {
+    window: usize,
+    recent: VecDeque<i64>,
+    counts: HashMap<i64, usize>,
+    seen: usize,
+}
+
+impl XmasValidator {
+    pub fn new(window: usize) -> Result<XmasValidator, String> {
+        if window < 2 {
+            return Err(format!("A window of {} can't hold a pair of numbers", window));
+        }
+        Ok(XmasValidator { window, recent: VecDeque::with_capacity(window + 1), counts: HashMap::new(), seen: 0 })
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    // How many numbers have been pushed so far.
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    // Whether two different entries in the window add up to value.
+    pub fn is_sum_of_pair(&self, value: i64) -> bool {
+        self.recent.iter().any(|first| match value.checked_sub(*first) {
+            Some(second) if second == *first => self.counts.get(&second).is_some_and(|count| *count >= 2),
+            Some(second) => self.counts.contains_key(&second),
+            None => false,
+        })
+    }
+
+    // Checks value against the window, then slides it in. Invalid numbers still join the
+    // window: each number is checked against the ones just before it, whatever they were.
+    pub fn push(&mut self, value: i64) -> Verdict {
+        let verdict = if self.recent.len() < self.window {
+            Verdict::Preamble
+        } else if self.is_sum_of_pair(value) {
+            Verdict::Valid
+        } else {
+            Verdict::Invalid
+        };
+
+        self.recent.push_back(value);
+        *self.counts.entry(value).or_insert(0) += 1;
+        if self.recent.len() > self.window {
+            let oldest = self.recent.pop_front().unwrap();
+            if let Some(count) = self.counts.get_mut(&oldest) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&oldest);
+                }
+            }
+        }
+
+        self.seen += 1;
+        verdict
+    }
+}
+
+// Every invalid number in values, in order.
+pub fn find_invalid_numbers<I: IntoIterator<Item = i64>>(values: I, window: usize) -> Result<Vec<InvalidNumber>, String> {
+    let mut validator = XmasValidator::new(window)?;
+    let mut invalid = Vec::new();
+
+    for (index, value) in values.into_iter().enumerate() {
+        if validator.push(value) == Verdict::Invalid {
+            invalid.push(InvalidNumber { index, value });
+        }
+    }
+
+    Ok(invalid)
+}
+
+// Like find_invalid_numbers, reading a number per line as it goes. Blank lines are skipped
+// and don't count towards the index.
+pub fn validate_reader<R: BufRead>(reader: R, window: usize) -> Result<Vec<InvalidNumber>, String> {
+    let mut validator = XmasValidator::new(window)?;
+    let mut invalid = Vec::new();
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("Couldn't read line {}: {}", line_no + 1, err))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let value: i64 = line.parse().map_err(|_| format!("Line {}: {} isn't a number", line_no + 1, line))?;
+        let index = validator.seen();
+        if validator.push(value) == Verdict::Invalid {
+            invalid.push(InvalidNumber { index, value });
+        }
+    }
+
+    Ok(invalid)
+}
+
+pub fn describe_invalid_numbers(invalid: &[InvalidNumber]) -> String {
+    if invalid.is_empty() {
+        return "Every number checks out.".to_string();
+    }
+
+    let mut lines = vec![format!("{} invalid number{}:", invalid.len(), if invalid.len() == 1 { "" } else { "s" })];
+    lines.extend(invalid.iter().map(|number| format!("#{}: {}", number.index, number.value)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod validatortests {
+    use super::*;
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+
+    fn get_puzzle_example() -> Vec<i64> {
+        vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576]
+    }
+
+    #[test]
+    fn pairs_must_be_two_different_entries() {
+        let mut validator = XmasValidator::new(3).unwrap();
+        for value in [5, 10, 20] {
+            assert_eq!(validator.push(value), Verdict::Preamble);
+        }
+
+        assert!(validator.is_sum_of_pair(15));
+        assert!(!validator.is_sum_of_pair(10));
+        assert!(!validator.is_sum_of_pair(40));
+        assert_eq!(validator.push(30), Verdict::Valid);
+
+        // 5 has left the window, so 10 + 5 doesn't count any more.
+        assert_eq!(validator.push(15), Verdict::Invalid);
+        assert_eq!(validator.push(45), Verdict::Valid);
+        assert!(XmasValidator::new(1).is_err());
+    }
+
+    #[test]
+    fn repeated_values_count_as_a_pair() {
+        let mut validator = XmasValidator::new(2).unwrap();
+        validator.push(7);
+        validator.push(7);
+
+        assert_eq!(validator.push(14), Verdict::Valid);
+        // One of the 7s has left the window.
+        assert_eq!(validator.push(14), Verdict::Invalid);
+    }
+
+    #[test]
+    fn every_invalid_number_is_reported() {
+        let mut values = get_puzzle_example();
+        values.push(1);
+
+        assert_eq!(find_invalid_numbers(get_puzzle_example(), 5), Ok(vec![InvalidNumber { index: 14, value: 127 }]));
+        assert_eq!(find_invalid_numbers(values, 5).unwrap().iter().map(|number| number.value).collect::<Vec<i64>>(), vec![127, 1]);
+    }
+
+    #[test]
+    fn readers_stream_and_report_bad_lines() {
+        let text = get_puzzle_example().iter().map(|value| value.to_string()).collect::<Vec<String>>().join("\n\n");
+        assert_eq!(validate_reader(Cursor::new(text), 5), Ok(vec![InvalidNumber { index: 14, value: 127 }]));
+
+        assert_eq!(validate_reader(Cursor::new("1\n2\nthree\n"), 2), Err("Line 3: three isn't a number".to_string()));
+    }
+
+    #[test]
+    fn puzzle_input_matches_the_first_star() {
+        let invalid = validate_reader(BufReader::new(File::open("inputs/day9.txt").unwrap()), DEFAULT_WINDOW).unwrap();
+        let bufreader = BufReader::new(File::open("inputs/day9.txt").unwrap());
+        let inputs: Vec<i64> = bufreader.lines().map(|line| line.unwrap().parse().unwrap()).collect();
+
+        assert_eq!(invalid.first().map(|number| number.value), crate::day9::xmas_encoding_find_first_invalid_number(&inputs, 25).ok());
+        assert_eq!(inputs[invalid[0].index], invalid[0].value);
+    }
+}
@@ -18,6 +18,19 @@ pub mod day11;
 pub mod day12;
 
 fn main() {
+    // Any arguments run a single command instead of the menus.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        match run_command(&args) {
+            Ok(output) => println!("{}", output),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // Creates the cursive root - required for every application.
     let mut siv =  Cursive::new(|| {
         let crossterm_backend = cursive::backends::crossterm::Backend::init().unwrap();
@@ -52,6 +65,14 @@ fn main() {
     siv.run();
 }
 
+fn run_command(args: &[String]) -> Result<String, String> {
+    match args {
+        [day, rest @ ..] if day == "day7" => day7::run_command(rest),
+        [day, rest @ ..] if day == "day9" => day9::run_command(rest),
+        _ => Err("usage: adventofcode2020 day7 query [--rules FILE] <query>\n       adventofcode2020 day9 validate [--window N] [FILE]".to_string()),
+    }
+}
+
 fn menu_selection(s: &mut Cursive, selection: &i32) {
     match selection {
         0 => {